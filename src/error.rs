@@ -0,0 +1,558 @@
+use std::fmt;
+
+use nom_supreme::error::{ErrorTree, BaseErrorKind, Expectation, StackContext};
+
+/// Error parsing a map file, locating the failure within the map
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MapError {
+    /// Line containing the failure (1-based)
+    pub line: usize,
+    /// Column of the failure in characters (1-based)
+    pub column: usize,
+    /// Block being parsed, where known
+    pub block: Option<MapBlock>,
+    /// Construct expected at the failure
+    pub expected: Expected,
+    /// Text of the failing line, without line ending
+    pub text: String,
+    /// Parser contexts, outermost first (ie. `map`, `memory config`, `memory`)
+    ///
+    /// These are diagnostic only and may change between releases,
+    /// use `block` and `expected` to handle errors instead
+    pub context: Vec<&'static str>,
+}
+
+/// Construct expected at a parse failure
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Expected {
+    /// Map file
+    Map,
+    /// Top-level block, by heading
+    Block,
+    /// Memory configuration block
+    MemoryConfig,
+    /// Memory region within the memory configuration
+    Memory,
+    /// Archive member references block
+    References,
+    /// Archive member reference
+    Reference,
+    /// As-needed libraries block
+    AsNeededLibraries,
+    /// As-needed library
+    AsNeededLibrary,
+    /// Common symbols block
+    CommonSymbols,
+    /// Common symbol
+    CommonSymbol,
+    /// Discarded input sections block
+    DiscardedSections,
+    /// Discarded input section
+    DiscardedSection,
+    /// Cross reference table
+    CrossReferences,
+    /// Cross reference entry
+    CrossReference,
+    /// Linker script and memory map block
+    MemoryMap,
+    /// Loaded input file (ie. `LOAD build/main.o`)
+    Load,
+    /// Linker script command (ie. `START GROUP`)
+    Command,
+    /// Output section
+    Section,
+    /// Output section type (ie. `(NOLOAD)`)
+    SectionType,
+    /// Input section specification (ie. `*(.text*)`)
+    InputSpec,
+    /// Input section pattern
+    Pattern,
+    /// Input section placed in an output section
+    Object,
+    /// Symbol line
+    Symbol,
+    /// Symbol name
+    SymbolName,
+    /// Symbols following an input section
+    Symbols,
+    /// Symbol assignment (ie. `_estack = 0x20010000`)
+    Assignment,
+    /// Linker script expression
+    Expression,
+    /// Data statement (ie. `LONG 0x0`)
+    Data,
+    /// Assertion
+    Assert,
+    /// Fill statement
+    Fill,
+    /// Fill mask
+    FillMask,
+    /// Input file path, or archive member
+    InputFile,
+    /// File path
+    Path,
+    /// Size before linker relaxation
+    SizeBeforeRelaxing,
+    /// Hexadecimal value (ie. `0x08000000`)
+    Hex,
+    /// Column indentation
+    Indentation,
+    /// End of the current line
+    EndOfLine,
+    /// End of the map
+    EndOfInput,
+    /// Further input, where the map is truncated
+    MoreInput,
+    /// Literal text
+    Tag(String),
+    /// Literal character
+    Char(char),
+    /// Any one of several constructs
+    OneOf(Vec<Expected>),
+    /// Other construct, by description
+    Other(String),
+}
+
+impl Expected {
+    /// Resolve an expected construct from a parser context
+    fn from_context(context: &str) -> Self {
+        match context {
+            "map" => Expected::Map,
+            "block" => Expected::Block,
+            "memory config" => Expected::MemoryConfig,
+            "memory" => Expected::Memory,
+            "references" => Expected::References,
+            "reference" => Expected::Reference,
+            "as-needed libraries" => Expected::AsNeededLibraries,
+            "as-needed library" => Expected::AsNeededLibrary,
+            "common symbols" => Expected::CommonSymbols,
+            "common symbol" => Expected::CommonSymbol,
+            "discarded sections" => Expected::DiscardedSections,
+            "discarded section" => Expected::DiscardedSection,
+            "cross references" => Expected::CrossReferences,
+            "cross reference" => Expected::CrossReference,
+            "memory map" => Expected::MemoryMap,
+            "load" => Expected::Load,
+            "command" => Expected::Command,
+            "section" => Expected::Section,
+            "section type" => Expected::SectionType,
+            "input spec" => Expected::InputSpec,
+            "pattern" => Expected::Pattern,
+            "object" => Expected::Object,
+            "symbol" => Expected::Symbol,
+            "symbol name" => Expected::SymbolName,
+            "symbols" => Expected::Symbols,
+            "assignment" => Expected::Assignment,
+            "expression" => Expected::Expression,
+            "data" => Expected::Data,
+            "assert" => Expected::Assert,
+            "fill" => Expected::Fill,
+            "fill mask" => Expected::FillMask,
+            "input file" => Expected::InputFile,
+            "path" => Expected::Path,
+            "size before relaxing" => Expected::SizeBeforeRelaxing,
+            "hex" => Expected::Hex,
+            "indentation" => Expected::Indentation,
+            "end of line" => Expected::EndOfLine,
+            c => Expected::Other(c.to_string()),
+        }
+    }
+
+    /// Resolve an expected construct from a base parser error
+    fn from_kind(kind: &BaseErrorKind<&'static str, Box<dyn std::error::Error + Send + Sync + 'static>>) -> Self {
+        match kind {
+            BaseErrorKind::Expected(Expectation::Eof) => Expected::EndOfInput,
+            BaseErrorKind::Expected(Expectation::Something) => Expected::MoreInput,
+            BaseErrorKind::Expected(Expectation::Tag(t)) => Expected::Tag(t.to_string()),
+            BaseErrorKind::Expected(Expectation::Char(c)) => Expected::Char(*c),
+            BaseErrorKind::Expected(e) => Expected::Other(e.to_string()),
+            BaseErrorKind::Kind(k) => Expected::Other(k.description().to_lowercase()),
+            BaseErrorKind::External(e) => Expected::Other(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Expected::Map => "map",
+            Expected::Block => "block",
+            Expected::MemoryConfig => "memory config",
+            Expected::Memory => "memory",
+            Expected::References => "references",
+            Expected::Reference => "reference",
+            Expected::AsNeededLibraries => "as-needed libraries",
+            Expected::AsNeededLibrary => "as-needed library",
+            Expected::CommonSymbols => "common symbols",
+            Expected::CommonSymbol => "common symbol",
+            Expected::DiscardedSections => "discarded sections",
+            Expected::DiscardedSection => "discarded section",
+            Expected::CrossReferences => "cross references",
+            Expected::CrossReference => "cross reference",
+            Expected::MemoryMap => "memory map",
+            Expected::Load => "load",
+            Expected::Command => "command",
+            Expected::Section => "section",
+            Expected::SectionType => "section type",
+            Expected::InputSpec => "input spec",
+            Expected::Pattern => "pattern",
+            Expected::Object => "object",
+            Expected::Symbol => "symbol",
+            Expected::SymbolName => "symbol name",
+            Expected::Symbols => "symbols",
+            Expected::Assignment => "assignment",
+            Expected::Expression => "expression",
+            Expected::Data => "data",
+            Expected::Assert => "assert",
+            Expected::Fill => "fill",
+            Expected::FillMask => "fill mask",
+            Expected::InputFile => "input file",
+            Expected::Path => "path",
+            Expected::SizeBeforeRelaxing => "size before relaxing",
+            Expected::Hex => "hex",
+            Expected::Indentation => "indentation",
+            Expected::EndOfLine => "end of line",
+            Expected::EndOfInput => "end of input",
+            Expected::MoreInput => "more input",
+            Expected::Tag(t) => return write!(f, "{:?}", t),
+            Expected::Char(c) => return write!(f, "{:?}", c),
+            Expected::OneOf(v) => return match v.split_last() {
+                Some((last, rest)) if !rest.is_empty() => {
+                    let rest: Vec<_> = rest.iter().map(|e| e.to_string() ).collect();
+                    write!(f, "{} or {}", rest.join(", "), last)
+                },
+                Some((last, _)) => write!(f, "{}", last),
+                None => write!(f, "input"),
+            },
+            Expected::Other(s) => s,
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Top-level map block or output section containing a parse failure
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum MapBlock {
+    /// Archive members included to satisfy references
+    References,
+    /// As-needed libraries
+    AsNeeded,
+    /// Allocating common symbols
+    CommonSymbols,
+    /// Discarded input sections
+    Discarded,
+    /// Memory configuration
+    Memory,
+    /// Linker script and memory map, outside of an output section
+    MemoryMap,
+    /// Output section within the memory map, by name where this has a header (ie. `.flash`)
+    Section(Option<String>),
+    /// Cross reference table
+    CrossReferences,
+}
+
+impl MapBlock {
+    /// Resolve a block from a parser context and its location
+    fn from_context(context: &str, location: &[u8]) -> Option<Self> {
+        let b = match context {
+            "references" => MapBlock::References,
+            "as-needed libraries" => MapBlock::AsNeeded,
+            "common symbols" => MapBlock::CommonSymbols,
+            "discarded sections" => MapBlock::Discarded,
+            "memory config" => MapBlock::Memory,
+            "memory map" => MapBlock::MemoryMap,
+            "section" => MapBlock::Section(section_name(location)),
+            "cross references" => MapBlock::CrossReferences,
+            _ => return None,
+        };
+        Some(b)
+    }
+}
+
+impl fmt::Display for MapBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapBlock::References => write!(f, "archive references"),
+            MapBlock::AsNeeded => write!(f, "as-needed libraries"),
+            MapBlock::CommonSymbols => write!(f, "common symbols"),
+            MapBlock::Discarded => write!(f, "discarded sections"),
+            MapBlock::Memory => write!(f, "memory config"),
+            MapBlock::MemoryMap => write!(f, "memory map"),
+            MapBlock::Section(Some(n)) => write!(f, "output section `{}`", n),
+            MapBlock::Section(None) => write!(f, "output section"),
+            MapBlock::CrossReferences => write!(f, "cross references"),
+        }
+    }
+}
+
+impl MapError {
+    /// Build an error from the parser error tree for the provided input,
+    /// following the alternative that progressed furthest
+    pub(crate) fn from_tree(input: &[u8], e: &ErrorTree<&[u8]>) -> Self {
+        let t = Trace::from_tree(e);
+
+        // Locate the failing line
+        let offset = input.len().saturating_sub(t.location.len());
+        let start = input[..offset].iter().rposition(|c| *c == b'\n' ).map(|i| i + 1 ).unwrap_or(0);
+        let end = input[offset..].iter().position(|c| *c == b'\r' || *c == b'\n' ).map(|i| offset + i ).unwrap_or(input.len());
+
+        let line = input[..start].iter().filter(|c| **c == b'\n' ).count() + 1;
+        let column = String::from_utf8_lossy(&input[start..offset]).chars().count() + 1;
+
+        let block = t.contexts.iter().find_map(|(l, c)| MapBlock::from_context(c, l) );
+
+        let mut context: Vec<_> = t.contexts.iter().rev().map(|(_, c)| *c ).collect();
+        context.dedup();
+
+        Self{
+            line,
+            column,
+            block,
+            expected: t.expected(),
+            text: String::from_utf8_lossy(&input[start..end]).into_owned(),
+            context,
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}", self.expected)?;
+        if let Some(b) = &self.block {
+            write!(f, " in {}", b)?;
+        }
+        write!(f, " at line {}, column {}", self.line, self.column)
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// Path through an error tree to the furthest failure
+struct Trace<'a> {
+    location: &'a [u8],
+    /// Parser contexts with their locations, innermost first
+    contexts: Vec<(&'a [u8], &'static str)>,
+    /// Expectations at the failure, used where no context is available
+    expected: Vec<Expected>,
+    /// Whether `expected` names alternative constructs, taking precedence over enclosing contexts
+    alternatives: bool,
+}
+
+impl <'a> Trace<'a> {
+    fn from_tree(e: &ErrorTree<&'a [u8]>) -> Self {
+        match e {
+            ErrorTree::Base{ location, kind } => Self{
+                location,
+                contexts: vec![],
+                expected: vec![Expected::from_kind(kind)],
+                alternatives: false,
+            },
+            ErrorTree::Stack{ base, contexts } => {
+                let mut t = Self::from_tree(base);
+                t.contexts.extend(contexts.iter().filter_map(|(l, c)| match c {
+                    StackContext::Context(c) => Some((*l, *c)),
+                    StackContext::Kind(_) => None,
+                }));
+                t
+            },
+            ErrorTree::Alt(v) => {
+                let mut traces: Vec<_> = v.iter().map(Self::from_tree).collect();
+                let furthest = traces.iter().map(|t| t.location.len() ).min().unwrap_or(0);
+                traces.retain(|t| t.location.len() == furthest );
+
+                if traces.len() == 1 {
+                    return traces.remove(0);
+                }
+
+                // Alternatives failing at the same location are all expected, where these
+                // are unnamed (ie. tags) the enclosing context names the construct instead
+                let alternatives = traces.iter().any(|t| t.alternatives || !t.contexts.is_empty() );
+                let mut expected: Vec<Expected> = vec![];
+                // Alternatives are named by their outermost context (ie. `object` rather than `hex`)
+                for e in traces.iter().map(|t| t.contexts.last().map(|(_, c)| Expected::from_context(c) ).unwrap_or_else(|| t.expected() ) ) {
+                    let v = match e {
+                        Expected::OneOf(v) => v,
+                        e => vec![e],
+                    };
+                    for e in v {
+                        if !expected.contains(&e) {
+                            expected.push(e);
+                        }
+                    }
+                }
+
+                Self{
+                    location: traces.first().map(|t| t.location ).unwrap_or_default(),
+                    contexts: vec![],
+                    expected,
+                    alternatives,
+                }
+            },
+        }
+    }
+
+    /// Describe the expected construct, the innermost context where available
+    fn expected(&self) -> Expected {
+        match (self.contexts.first(), self.expected.as_slice()) {
+            (Some((_, c)), _) if !self.alternatives => Expected::from_context(c),
+            (_, [e]) => e.clone(),
+            (_, v) => Expected::OneOf(v.to_vec()),
+        }
+    }
+}
+
+/// Fetch the name of the output section starting at the provided location (ie. `.flash`),
+/// sections without a header are unnamed
+fn section_name(s: &[u8]) -> Option<String> {
+    let start = s.iter().position(|c| *c != b'\r' && *c != b'\n' )?;
+    let s = &s[start..];
+
+    let end = s.iter().position(|c| c.is_ascii_whitespace() ).unwrap_or(s.len());
+    match end {
+        0 => None,
+        _ => Some(String::from_utf8_lossy(&s[..end]).into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MapFile;
+
+    use pretty_assertions::assert_eq;
+
+    const MAP: &str = "Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00100000         xr
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+LOAD build/main.o
+
+.flash          0x08000000      0x110
+ *(.text*)
+ .text          0x08000000      0x100 build/main.o
+                0x08000000                main
+ .text          0x08000100       0x10 build/other.o
+";
+
+    #[test]
+    fn parse_errors() {
+        let tests = [
+            (
+                MAP.replace("*default*        0x00000000", "*default*        zzz"),
+                MapError{
+                    line: 5,
+                    column: 18,
+                    block: Some(MapBlock::Memory),
+                    expected: Expected::Hex,
+                    text: "*default*        zzz         0xffffffff".to_string(),
+                    context: vec!["map", "memory config", "memory", "hex"],
+                },
+            ), (
+                MAP.replace("0x100 build/main.o", "0x100 build/main.o\n %%% oops"),
+                MapError{
+                    line: 14,
+                    column: 6,
+                    block: Some(MapBlock::Section(Some(".flash".to_string()))),
+                    expected: Expected::OneOf(vec![Expected::InputSpec, Expected::Object]),
+                    text: " %%% oops".to_string(),
+                    context: vec!["map", "memory map", "section"],
+                },
+            ), (
+                // Truncated maps report the incomplete line
+                MAP.replace("       0x10 build/other.o\n", "       0x"),
+                MapError{
+                    line: 15,
+                    column: 36,
+                    block: Some(MapBlock::Section(Some(".flash".to_string()))),
+                    expected: Expected::Hex,
+                    text: " .text          0x08000100       0x".to_string(),
+                    context: vec!["map", "memory map", "section", "object", "hex"],
+                },
+            ), (
+                // Discarded sections are not output sections
+                format!("Discarded input sections\n\n .text.unused   0x00000000       0xzz build/main.o\n\n{}", MAP),
+                MapError{
+                    line: 3,
+                    column: 36,
+                    block: Some(MapBlock::Discarded),
+                    expected: Expected::Hex,
+                    text: " .text.unused   0x00000000       0xzz build/main.o".to_string(),
+                    context: vec!["map", "discarded sections", "discarded section", "hex"],
+                },
+            ), (
+                format!("Unknown heading\n\nbody\n\n    stray\n\n{}", MAP),
+                MapError{
+                    line: 5,
+                    column: 1,
+                    block: None,
+                    expected: Expected::Block,
+                    text: "    stray".to_string(),
+                    context: vec!["map", "block"],
+                },
+            ), (
+                // Malformed section headers are reported rather than ending the memory map
+                MAP.replace(".flash          0x08000000      0x110", ".flash          0x0800000g      0x110"),
+                MapError{
+                    line: 11,
+                    column: 26,
+                    block: Some(MapBlock::Section(Some(".flash".to_string()))),
+                    expected: Expected::Hex,
+                    text: ".flash          0x0800000g      0x110".to_string(),
+                    context: vec!["map", "memory map", "section", "hex"],
+                },
+            ), (
+                // As are unknown commands
+                MAP.replace("LOAD build/main.o\n", "LOAD build/main.o\nINSERT AFTER .text\n"),
+                MapError{
+                    line: 10,
+                    column: 1,
+                    block: Some(MapBlock::MemoryMap),
+                    expected: Expected::OneOf(vec![Expected::Command, Expected::Assignment, Expected::Section]),
+                    text: "INSERT AFTER .text".to_string(),
+                    context: vec!["map", "memory map"],
+                },
+            ), (
+                // And unknown blocks following the memory map
+                format!("{}\nUnknown heading\n\n    stray\n", MAP),
+                MapError{
+                    line: 17,
+                    column: 1,
+                    block: Some(MapBlock::MemoryMap),
+                    expected: Expected::OneOf(vec![Expected::Command, Expected::Assignment, Expected::Section]),
+                    text: "Unknown heading".to_string(),
+                    context: vec!["map", "memory map"],
+                },
+            ),
+        ];
+
+        for (raw, e) in tests {
+            assert_eq!(MapFile::parse(&raw), Err(e));
+        }
+    }
+
+    #[test]
+    fn reject_unrecognised() {
+        // Unrecognised blocks require a blank line or indented body following the heading
+        assert!(MapFile::parse("garbage").is_err());
+        assert!(MapFile::parse("garbage\nmore garbage\n").is_err());
+        assert!(MapFile::parse("Future block\n\n").is_ok());
+
+        // Trailing text following a heading is not a new block
+        assert!(MapFile::parse(&MAP.replace("Linker script and memory map", "Linker script and memory map (partial)")).is_err());
+    }
+
+    #[test]
+    fn format_errors() {
+        let e = MapFile::parse(&MAP.replace("0x00100000", "0x0010zz00")).unwrap_err();
+        assert_eq!(e.to_string(), "expected hex in memory config at line 4, column 43");
+
+        let e = Expected::OneOf(vec![Expected::InputSpec, Expected::Object, Expected::Tag("*default*".to_string())]);
+        assert_eq!(e.to_string(), r#"input spec, object or "*default*""#);
+    }
+}
@@ -1,31 +1,47 @@
 use nom::{
-    IResult,
+    IResult, Parser,
     bytes::complete::{tag, take_while1},
     sequence::{tuple, terminated, preceded, delimited},
     multi::{many0},
-    error::{context}, 
-    character::{complete::{line_ending, space1, space0}, is_hex_digit}, combinator::{rest, map_res, opt},
+    error::{context, ErrorKind, ParseError, ContextError}, branch::alt,
+    character::{complete::{line_ending, space0, space1, multispace0, satisfy}, is_hex_digit}, combinator::{map, map_res, eof, recognize, not},
 };
 
 use nom_supreme::{
-    final_parser::{final_parser, Location},
+    final_parser::final_parser,
     error::ErrorTree
 };
 
+use std::collections::{BTreeMap, BTreeSet};
 
-use log::{trace, debug};
+use log::debug;
 
 pub mod objects;
 use objects::*;
 
+mod error;
+pub use error::*;
+
 /// Linker .map file object for parsing
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Default)]
 pub struct MapFile<'a> {
     pub references: Vec<ArchiveInfo<'a>>,
+    pub as_needed: Vec<AsNeededInfo<'a>>,
+    pub common_symbols: Vec<CommonSymbolInfo<'a>>,
     pub discarded: Vec<SectionInfo<'a>>,
     pub memory: Vec<MemoryInfo<'a>>,
     pub files: Vec<FileInfo<'a>>,
-    pub sections: Vec<Object<'a>>,
+    /// Linker commands (ie. `LOAD`, `START GROUP`, `OUTPUT(...)`), in map order
+    pub commands: Vec<Command<'a>>,
+    /// Top-level linker script assignments (ie. `_estack = main_stack_base`)
+    pub assignments: Vec<Assignment<'a>>,
+    pub sections: Vec<Section<'a>>,
+    /// Cross reference table (from `--cref`)
+    pub cross_references: Vec<CrossReference<'a>>,
+    /// Unrecognised blocks outside of the memory map, preserved as raw text
+    pub other: Vec<RawBlock<'a>>,
+    /// Address width in bits (ie. 32 for `0x08040000`), detected from padded addresses
+    pub address_bits: Option<u32>,
 }
 
 /// Map file information
@@ -41,8 +57,349 @@ pub struct MapInfo {
 
 impl <'a> MapFile<'a> {
 
-    pub fn parse(s: &'a str) -> Result<Self, ErrorTree<&'a str>> {
+    /// Parse a map from text, see [`MapFile::parse_bytes`] for maps that may not be valid UTF-8
+    pub fn parse(s: &'a str) -> Result<Self, MapError> {
+        Self::parse_bytes(s.as_bytes())
+    }
+
+    /// Parse a map from raw bytes, names and paths are preserved as byte slices
+    /// so maps with non-UTF-8 paths or symbols may be loaded
+    pub fn parse_bytes(s: &'a [u8]) -> Result<Self, MapError> {
         final_parser(MapFile::parse_internal)(s)
+            .map_err(|e: ErrorTree<&[u8]>| MapError::from_tree(s, &e) )
+    }
+
+    /// Fetch the output sections allocated in target memory, excluding debug and metadata sections
+    pub fn alloc_sections(&self) -> impl Iterator<Item=&Section<'a>> {
+        self.sections.iter().filter(|s| s.is_alloc() )
+    }
+
+    /// Format an address zero-padded to the detected address width (ie. `0x08040000`),
+    /// defaulting to 64-bit
+    pub fn format_addr(&self, addr: u64) -> String {
+        let digits = self.address_bits.unwrap_or(64) as usize / 4;
+        format!("{:#0w$x}", addr, w = digits + 2)
+    }
+
+    /// Fetch an output section by name (ie. `.flash`)
+    pub fn section(&self, name: &str) -> Option<&Section<'a>> {
+        self.sections.iter().find(|s| matches!(s.name, Some(n) if n == name) )
+    }
+
+    /// Fetch the output file name (from `OUTPUT(...)`)
+    pub fn output_file(&self) -> Option<FilePath<'a>> {
+        self.commands.iter().find_map(|c| match c {
+            Command::Output{ file, .. } => Some(*file),
+            _ => None,
+        })
+    }
+
+    /// Fetch the output BFD target, from `OUTPUT(...)` or `OUTPUT_FORMAT(...)`
+    pub fn output_target(&self) -> Option<BfdTarget<'a>> {
+        let output = self.commands.iter().find_map(|c| match c {
+            Command::Output{ target, .. } => *target,
+            _ => None,
+        });
+
+        output.or_else(|| self.commands.iter().find_map(|c| match c {
+            Command::OutputFormat(t) => Some(*t),
+            _ => None,
+        }))
+    }
+
+    /// Fetch the files loaded within the provided link group
+    pub fn group(&self, index: usize) -> impl Iterator<Item=&FileInfo<'a>> {
+        self.files.iter().filter(move |f| f.group == Some(index) )
+    }
+
+    /// Fetch all linker script assignments (including location counter moves),
+    /// top-level then per section, in map order
+    pub fn all_assignments(&self) -> impl Iterator<Item=&Assignment<'a>> {
+        let sections = self.sections.iter().flat_map(|s| s.entries.iter() ).filter_map(|e| match e {
+            Entry::Assignment(a) | Entry::Move(a) => Some(a),
+            _ => None,
+        });
+
+        self.assignments.iter().chain(sections)
+    }
+
+    /// Build a table of linker-defined symbols (excluding the location counter and
+    /// unapplied `PROVIDE` assignments), where symbols are assigned more than once
+    /// the last assignment is used
+    pub fn linker_symbols(&self) -> BTreeMap<Name<'a>, &Assignment<'a>> {
+        self.all_assignments()
+            .filter(|a| !a.is_location_counter() && a.is_applied() )
+            .map(|a| (a.symbol, a) )
+            .collect()
+    }
+
+    /// Fetch the value of a linker-defined or output symbol
+    pub fn symbol_value(&self, name: &str) -> Option<u64> {
+        if let Some(v) = self.linker_symbols().get(name.as_bytes()).and_then(|a| a.value ) {
+            return Some(v);
+        }
+
+        self.sections.iter()
+            .flat_map(|s| s.symbols() )
+            .find(|s| matches!(s.kind, SymbolKind::Value(n) if n == name) )
+            .map(|s| s.addr )
+    }
+
+    /// Evaluate an assignment expression against the regions, sections and symbols in the map
+    pub fn evaluate(&self, assignment: &Assignment<'a>) -> Result<u64, EvalError<'a>> {
+        let e = assignment.expression().ok_or(EvalError::Syntax(assignment.expr))?;
+        e.evaluate(self, None)
+    }
+
+    /// Check an assignment evaluates to the value reported by the linker
+    pub fn verify(&self, assignment: &Assignment<'a>) -> Result<bool, EvalError<'a>> {
+        let v = self.evaluate(assignment)?;
+        Ok(assignment.value == Some(v))
+    }
+
+    /// Resolve the regions, sections and symbols a linker-defined symbol depends on,
+    /// following assignments through other linker-defined symbols
+    pub fn symbol_dependencies(&self, symbol: &'a str) -> BTreeSet<Dependency<'a>> {
+        let symbols = self.linker_symbols();
+
+        let mut deps = BTreeSet::new();
+        let mut pending = vec![symbol];
+
+        while let Some(s) = pending.pop() {
+            let e = match symbols.get(s.as_bytes()).and_then(|a| a.expression() ) {
+                Some(e) => e,
+                None => continue,
+            };
+
+            for d in e.dependencies() {
+                if let Dependency::Symbol(n) = d {
+                    if n != symbol && !deps.contains(&d) {
+                        pending.push(n);
+                    }
+                }
+                deps.insert(d);
+            }
+        }
+
+        deps
+    }
+
+    /// Fetch the references causing members of the provided archive to be included
+    pub fn archive_references<'b>(&'b self, archive: &'b str) -> impl Iterator<Item=&'b ArchiveInfo<'a>> + 'b {
+        self.references.iter().filter(move |r| r.archive == archive )
+    }
+
+    /// Fetch the cross reference entry for a symbol
+    pub fn cross_reference(&self, symbol: &str) -> Option<&CrossReference<'a>> {
+        self.cross_references.iter().find(|c| c.symbol == symbol )
+    }
+
+    /// Fetch the files defining a symbol (requires `--cref`), these are the listed files
+    /// the memory map places the symbol in, or otherwise the first listed file as ld lists
+    /// definitions first. Symbols defined by an applied linker script assignment have no
+    /// defining files, while skipped `PROVIDE` assignments (`[!provide]`) are ignored
+    pub fn definitions(&self, symbol: &str) -> Vec<InputFile<'a>> {
+        let c = match self.cross_reference(symbol) {
+            Some(c) if !self.linker_symbols().contains_key(symbol.as_bytes()) => c,
+            _ => return vec![],
+        };
+
+        let placed: Vec<_> = self.sections.iter()
+            .flat_map(|s| s.objects() )
+            .filter(|o| o.symbols.iter().any(|v| matches!(v.kind, SymbolKind::Value(n) if n == symbol) ) )
+            .filter_map(|o| o.source )
+            .collect();
+
+        let defs: Vec<_> = c.files.iter().copied().filter(|f| placed.contains(f) ).collect();
+        match defs.is_empty() {
+            true => c.files.first().copied().into_iter().collect(),
+            false => defs,
+        }
+    }
+
+    /// Fetch the file defining a symbol (requires `--cref`), the first of [`MapFile::definitions`]
+    pub fn defined_by(&self, symbol: &str) -> Option<InputFile<'a>> {
+        self.definitions(symbol).first().copied()
+    }
+
+    /// Fetch the files referencing a symbol (requires `--cref`), excluding [`MapFile::definitions`]
+    pub fn referenced_by(&self, symbol: &str) -> Vec<InputFile<'a>> {
+        let defs = self.definitions(symbol);
+        self.cross_reference(symbol)
+            .map(|c| c.files.iter().copied().filter(|f| !defs.contains(f) ).collect() )
+            .unwrap_or_default()
+    }
+
+    /// Compute bytes saved by linker relaxation for each allocated output section
+    pub fn relaxation_by_section(&self) -> BTreeMap<Name<'a>, u64> {
+        let mut savings = BTreeMap::new();
+
+        for s in self.alloc_sections() {
+            let v = s.relaxation_savings();
+            if let (Some(name), true) = (s.name, v > 0) {
+                *savings.entry(name).or_default() += v;
+            }
+        }
+
+        savings
+    }
+
+    /// Compute bytes saved by linker relaxation for each input file (or archive member),
+    /// within allocated output sections
+    pub fn relaxation_by_file(&self) -> BTreeMap<InputFile<'a>, u64> {
+        let mut savings = BTreeMap::new();
+
+        for o in self.alloc_sections().flat_map(|s| s.objects() ) {
+            let v = o.relaxation_savings();
+            if let (Some(source), true) = (o.source, v > 0) {
+                *savings.entry(source).or_default() += v;
+            }
+        }
+
+        savings
+    }
+
+    /// Compute bytes saved by linker relaxation for each input file, with archive members
+    /// grouped by archive
+    pub fn relaxation_by_archive(&self) -> BTreeMap<FilePath<'a>, u64> {
+        let mut savings = BTreeMap::new();
+
+        for (f, v) in self.relaxation_by_file() {
+            *savings.entry(f.path()).or_default() += v;
+        }
+
+        savings
+    }
+
+    /// Fetch the memory region containing the provided address,
+    /// preferring named regions over `*default*`
+    pub fn region(&self, addr: u64) -> Option<&MemoryInfo<'a>> {
+        self.memory.iter()
+            .filter(|m| m.contains(addr) )
+            .min_by_key(|m| m.name == "*default*" )
+    }
+
+    /// Compute bytes used in each memory region by allocated sections, counting sections
+    /// against both their runtime (VMA) and load (LMA) regions where these differ
+    /// (zero-initialised sections have no load image despite ld reporting a load address,
+    /// and overlay members share the runtime space of the largest member)
+    pub fn usage_by_region(&self) -> BTreeMap<Name<'a>, u64> {
+        let mut usage = BTreeMap::new();
+
+        // Overlay members other than the largest do not use additional runtime space
+        let shared: Vec<Name> = self.overlays().iter().flat_map(|o| {
+            let largest = o.sections.iter()
+                .max_by_key(|n| (self.sections.iter().find(|s| s.name == Some(**n) ).and_then(|s| s.size ), std::cmp::Reverse(**n)) )
+                .copied();
+            o.sections.iter().copied().filter(move |n| Some(*n) != largest )
+        }).collect();
+
+        for s in self.alloc_sections() {
+            let (addr, size) = match (s.addr, s.size) {
+                (Some(a), Some(s)) if s > 0 => (a, s),
+                _ => continue,
+            };
+
+            let vma = self.region(addr)
+                .filter(|_| !s.name.map(|n| shared.contains(&n) ).unwrap_or(false) )
+                .map(|m| m.name );
+            let lma = s.load_addr
+                .filter(|_| s.class() != SectionClass::ZeroInit )
+                .and_then(|a| self.region(a) )
+                .map(|m| m.name );
+
+            if let Some(name) = vma {
+                *usage.entry(name).or_default() += size;
+            }
+            if let Some(name) = lma.filter(|n| Some(*n) != vma ) {
+                *usage.entry(name).or_default() += size;
+            }
+        }
+
+        usage
+    }
+
+    /// Fetch overlay groups, allocated sections sharing a runtime address with differing load addresses
+    /// (empty sections do not occupy their address, so are not overlay members)
+    pub fn overlays(&self) -> Vec<Overlay<'a>> {
+        let mut overlays: Vec<Overlay> = vec![];
+        let mut prev: Option<&Section> = None;
+
+        for s in self.alloc_sections() {
+            let (name, addr) = match (s.name, s.addr, s.size) {
+                (Some(n), Some(a), Some(l)) if l > 0 => (n, a),
+                _ => continue,
+            };
+
+            match prev {
+                Some(p) if p.addr == Some(addr) && p.lma() != s.lma() => match overlays.last_mut() {
+                    Some(o) if o.sections.last() == p.name.as_ref() => o.sections.push(name),
+                    _ => overlays.push(Overlay{ addr, sections: vec![p.name.unwrap_or_default(), name] }),
+                },
+                _ => (),
+            }
+
+            prev = Some(s);
+        }
+
+        overlays
+    }
+
+    /// Find allocated sections with overlapping runtime addresses,
+    /// excluding members of the same overlay group
+    pub fn collisions(&self) -> Vec<(&Section<'a>, &Section<'a>)> {
+        let overlays = self.overlays();
+        let overlay = |name: Option<Name>| overlays.iter().position(|o| name.map(|n| o.sections.contains(&n) ).unwrap_or(false) );
+
+        let sections: Vec<_> = self.alloc_sections()
+            .filter_map(|s| match (s.addr, s.size) {
+                (Some(a), Some(l)) if l > 0 => Some((s, a, a + l)),
+                _ => None,
+            })
+            .collect();
+
+        let mut collisions = vec![];
+        for (i, (a, a_start, a_end)) in sections.iter().enumerate() {
+            for (b, b_start, b_end) in &sections[i+1..] {
+                if a_start >= b_end || b_start >= a_end {
+                    continue;
+                }
+
+                match overlay(a.name) {
+                    Some(o) if overlay(b.name) == Some(o) => continue,
+                    _ => collisions.push((*a, *b)),
+                }
+            }
+        }
+
+        collisions
+    }
+
+    /// Compute padding (`*fill*`) bytes for each allocated output section
+    pub fn padding_by_section(&self) -> BTreeMap<Name<'a>, u64> {
+        let mut padding = BTreeMap::new();
+
+        for s in self.alloc_sections() {
+            let v = s.padding();
+            if let (Some(name), true) = (s.name, v > 0) {
+                *padding.entry(name).or_default() += v;
+            }
+        }
+
+        padding
+    }
+
+    /// Compute padding (`*fill*`) bytes for each memory region, within allocated sections
+    pub fn padding_by_region(&self) -> BTreeMap<Name<'a>, u64> {
+        let mut padding = BTreeMap::new();
+
+        for f in self.alloc_sections().flat_map(|s| s.fills() ) {
+            if let Some(m) = self.region(f.addr) {
+                *padding.entry(m.name).or_default() += f.size;
+            }
+        }
+
+        padding
     }
 
     pub fn info(&self) -> MapInfo {
@@ -54,68 +411,291 @@ impl <'a> MapFile<'a> {
         }
     }
 
-    fn parse_internal(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
-        let (o, (_, _, references, discarded, memory, _,files, sections, rest)) = context(
+    fn parse_internal(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (_, blocks, _)) = context(
             "map",
             tuple((
-                space0,
-                opt(line_ending),
-                ArchiveInfo::parse_block,
-                SectionInfo::parse_block,
-                MemoryInfo::parse_block,
-                
-                terminated(tag("Linker script and memory map"), many0(line_ending)),
-                many0(terminated(FileInfo::parse, line_ending)),
-
-                many0(delimited(space0, Object::parse, line_ending)),
-
-                // Read the (unhandled) remains of the file
-                rest,
+                multispace0,
+                // Blocks are detected by heading, and may be absent or reordered
+                parse_items(
+                    terminated(Block::parse, parse_blank_lines),
+                    |s| s.trim_ascii_start().is_empty(),
+                ),
+                multispace0,
             ))
         )(s)?;
 
-        println!("remainder: {:#?}", rest);
+        let mut m = Self::default();
+        for b in blocks {
+            match b {
+                Block::References(mut v) => m.references.append(&mut v),
+                Block::AsNeeded(mut v) => m.as_needed.append(&mut v),
+                Block::CommonSymbols(mut v) => m.common_symbols.append(&mut v),
+                Block::Discarded(mut v) => m.discarded.append(&mut v),
+                Block::Memory(mut v) => m.memory.append(&mut v),
+                Block::MemoryMap(items) => for i in items {
+                    match i {
+                        MapItem::Command(c) => m.commands.push(c),
+                        MapItem::Assignment(a) => m.assignments.push(a),
+                        MapItem::Section(s) => m.sections.push(s),
+                    }
+                },
+                Block::CrossReferences(mut v) => m.cross_references.append(&mut v),
+                Block::Other(v) => m.other.push(v),
+            }
+        }
 
+        m.address_bits = detect_address_bits(s);
 
-        let m = Self {
-            references,
-            discarded,
-            memory,
-            files,
-            sections,
-        };
+        // Resolve loaded files and their link groups
+        let mut group = None;
+        let mut groups = 0;
+        for c in &m.commands {
+            match c {
+                Command::Load(name) => m.files.push(FileInfo{ name: *name, group }),
+                Command::StartGroup => {
+                    group = Some(groups);
+                    groups += 1;
+                },
+                Command::EndGroup => group = None,
+                _ => (),
+            }
+        }
 
-        println!("Parsed map ({} refs, {} discarded, {} memories, {} files, {} sections)", 
+        debug!("Parsed map ({} refs, {} discarded, {} memories, {} files, {} sections, {} other blocks)",
             m.references.len(),
             m.discarded.len(),
             m.memory.len(),
             m.files.len(),
             m.sections.len(),
+            m.other.len(),
         );
 
         Ok((o, m))
     }
 }
 
+/// Top-level blocks within a map file
+#[derive(Clone, PartialEq, Debug)]
+enum Block<'a> {
+    References(Vec<ArchiveInfo<'a>>),
+    AsNeeded(Vec<AsNeededInfo<'a>>),
+    CommonSymbols(Vec<CommonSymbolInfo<'a>>),
+    Discarded(Vec<SectionInfo<'a>>),
+    Memory(Vec<MemoryInfo<'a>>),
+    MemoryMap(Vec<MapItem<'a>>),
+    CrossReferences(Vec<CrossReference<'a>>),
+    Other(RawBlock<'a>),
+}
+
+impl <'a> Block<'a> {
+    fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let r = alt((
+            map(ArchiveInfo::parse_block, Block::References),
+            map(AsNeededInfo::parse_block, Block::AsNeeded),
+            map(CommonSymbolInfo::parse_block, Block::CommonSymbols),
+            map(SectionInfo::parse_block, Block::Discarded),
+            map(MemoryInfo::parse_block, Block::Memory),
+            map(Self::parse_memory_map, Block::MemoryMap),
+            map(CrossReference::parse_block, Block::CrossReferences),
+            // Preserve unrecognised blocks, outside of the memory map
+            map(RawBlock::parse, Block::Other),
+        ))(s);
 
+        // Report lines outside of any block as such, rather than against each block
+        match r {
+            Err(nom::Err::Error(_)) => {
+                let e = ErrorTree::from_error_kind(s, ErrorKind::Verify);
+                Err(nom::Err::Error(ErrorTree::add_context(s, "block", e)))
+            },
+            r => r,
+        }
+    }
 
-fn from_hex(input: &str) -> Result<u64, std::num::ParseIntError> {
-    u64::from_str_radix(input, 16)
+    fn parse_memory_map(s: &'a [u8]) -> IResult<&'a [u8], Vec<MapItem<'a>>, ErrorTree<&'a [u8]>> {
+        let (o, (_, items)) = context(
+            "memory map",
+            tuple((
+                terminated(tag("Linker script and memory map"), tuple((eol, many0(line_ending)))),
+                // The memory map continues to the next known heading, so unrecognised lines
+                // are reported rather than starting an unrecognised block
+                parse_items(alt((
+                    map(preceded(parse_blank_lines, Command::parse), MapItem::Command),
+                    map(Assignment::parse, MapItem::Assignment),
+                    map(Section::parse, MapItem::Section),
+                )), |s| {
+                    let s = s.trim_ascii_start();
+                    s.is_empty() || parse_heading(s).is_ok()
+                }),
+            ))
+        )(s)?;
+
+        Ok((o, items))
+    }
 }
 
-fn parse_hex(s: &str) -> IResult<&str, u64, ErrorTree<&str>> {
+/// Lines within the memory map block
+#[derive(Clone, PartialEq, Debug)]
+enum MapItem<'a> {
+    Command(Command<'a>),
+    Assignment(Assignment<'a>),
+    Section(Section<'a>),
+}
+
+/// Match known block headings
+fn parse_heading(s: &[u8]) -> IResult<&[u8], &[u8], ErrorTree<&[u8]>> {
+    alt((
+        tag("Archive member included to satisfy reference by file (symbol)"),
+        tag("As-needed library included to satisfy reference by file (symbol)"),
+        tag("Allocating common symbols"),
+        tag("Discarded input sections"),
+        tag("There are no discarded input sections"),
+        tag("Memory Configuration"),
+        tag("Linker script and memory map"),
+        tag("Cross Reference Table"),
+    ))(s)
+}
+
+/// Parse items until the provided end of block, failing where a line within
+/// the block is not recognised rather than ending the block early
+fn parse_items<'a, O>(
+    mut item: impl Parser<&'a [u8], O, ErrorTree<&'a [u8]>>,
+    is_end: impl Fn(&'a [u8]) -> bool,
+) -> impl Parser<&'a [u8], Vec<O>, ErrorTree<&'a [u8]>> {
+    move |mut s: &'a [u8]| {
+        let mut items = vec![];
+        loop {
+            match item.parse(s) {
+                // Items must consume input to avoid matching forever
+                Ok((o, v)) if o.len() < s.len() => {
+                    items.push(v);
+                    s = o;
+                },
+                Ok(_) => return Ok((s, items)),
+                Err(nom::Err::Error(_)) if is_end(s) => return Ok((s, items)),
+                Err(nom::Err::Error(e)) => return Err(nom::Err::Failure(e)),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Check for the end of a block, at a blank line, known heading or the end of input
+fn is_block_end(s: &[u8]) -> bool {
+    let r: IResult<_, _, ErrorTree<&[u8]>> = alt((
+        eof,
+        recognize(tuple((space0, line_ending))),
+        parse_heading,
+    ))(s);
+    r.is_ok()
+}
+
+/// Match (and discard) blank lines
+fn parse_blank_lines(s: &[u8]) -> IResult<&[u8], (), ErrorTree<&[u8]>> {
+    map(many0(preceded(space0, line_ending)), |_| () )(s)
+}
+
+
+
+/// Detect the address width from the widest address column, as addresses are zero-padded
+/// to the target width while sizes and values are not. Address columns hold the first value
+/// on a line, following at most a name (ie. memory origins, section, object and symbol
+/// addresses), so data values, expressions and other blocks are ignored
+fn detect_address_bits(s: &[u8]) -> Option<u32> {
+    let digits = s.split(|c| *c == b'\n' )
+        .filter_map(|line| {
+            let mut words = line.split(|c| c.is_ascii_whitespace() ).filter(|w| !w.is_empty() );
+            let word = match words.next()? {
+                w if w.starts_with(b"0x") => w,
+                _ => words.next()?,
+            };
+            let hex = word.strip_prefix(b"0x")?;
+            Some(hex.iter().take_while(|c| c.is_ascii_hexdigit() ).count())
+        })
+        .max()?;
+
+    match digits {
+        8 => Some(32),
+        9..=16 => Some(64),
+        _ => None,
+    }
+}
+
+fn from_hex(input: &[u8]) -> Result<u64, std::num::ParseIntError> {
+    // Hex digits are ASCII, so always valid UTF-8
+    u64::from_str_radix(std::str::from_utf8(input).unwrap_or_default(), 16)
+}
+
+fn parse_hex(s: &[u8]) -> IResult<&[u8], u64, ErrorTree<&[u8]>> {
     context(
         "hex",
         map_res(
-            preceded(tag("0x"), take_while1(|c| is_hex_digit(c as u8) )),
+            // Values run to the end of the word, rejecting ie. `0x0800000g`
+            delimited(tag("0x"), take_while1(is_hex_digit), not(satisfy(|c| c.is_ascii_alphanumeric() ))),
             from_hex,
         )
     )(s)
 }
 
-fn parse_path(s: &str) -> IResult<&str, &str, ErrorTree<&str>> {
+fn parse_path(s: &[u8]) -> IResult<&[u8], Name<'_>, ErrorTree<&[u8]>> {
     context(
         "path",
-        take_while1(|c| c != ' ' && c != '\r' && c != '\n')
+        map(take_while1(|c| c != b' ' && c != b'\r' && c != b'\n'), Name)
+    )(s)
+}
+
+/// Parse the `0x... (size before relaxing)` line following a relaxed input section
+fn parse_pre_relax_size(s: &[u8]) -> IResult<&[u8], u64, ErrorTree<&[u8]>> {
+    context(
+        "size before relaxing",
+        delimited(
+            space1,
+            parse_hex,
+            tuple((space1, tag("(size before relaxing)"), eol)),
+        )
+    )(s)
+}
+
+/// Match the separator following a name column, which is wrapped onto
+/// the next line where the name is longer than the column width
+fn parse_column_sep(s: &[u8]) -> IResult<&[u8], &[u8], ErrorTree<&[u8]>> {
+    alt((
+        space1,
+        recognize(tuple((line_ending, space1))),
+    ))(s)
+}
+
+/// Parse a name padded to a fixed column width and the following separator,
+/// allowing spaces within the name (ie. `C:\My Project\libc.a(memcpy.o)`).
+/// Names exceeding the column are wrapped onto their own line
+fn parse_column<'a>(width: usize) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Name<'a>, ErrorTree<&'a [u8]>> {
+    move |s: &'a [u8]| {
+        let end = s.iter().position(|c| *c == b'\r' || *c == b'\n' ).unwrap_or(s.len());
+        let line = &s[..end];
+
+        if line.is_empty() || line[0].is_ascii_whitespace() {
+            return Err(nom::Err::Error(ErrorTree::from_error_kind(s, ErrorKind::Verify)));
+        }
+
+        // Padded to the column width, followed by the next field
+        if line.len() > width && line[..width].ends_with(b"  ") {
+            return Ok((&s[width..], Name(line[..width].trim_ascii_end())));
+        }
+
+        // Wrapped, with the next field on the following line
+        if let Ok((o, _)) = tuple((line_ending, space1::<_, ErrorTree<&[u8]>>))(&s[end..]) {
+            return Ok((o, Name(line.trim_ascii_end())));
+        }
+
+        // Otherwise fall back to a single word for maps not using the standard widths
+        terminated(parse_path, parse_column_sep)(s)
+    }
+}
+
+/// Match the end of a line, ignoring trailing whitespace
+fn eol(s: &[u8]) -> IResult<&[u8], &[u8], ErrorTree<&[u8]>> {
+    context(
+        "end of line",
+        preceded(space0, alt((line_ending, eof)))
     )(s)
 }
@@ -1,7 +1,9 @@
-use mapfile::MapFile;
+use std::fmt::Write;
+
+use mapfile::{MapFile, MapError};
 use clap::Parser;
 
-use log::{debug, info, warn, LevelFilter};
+use log::{debug, info, LevelFilter};
 use simplelog::SimpleLogger;
 
 #[derive(PartialEq, Debug, Parser)]
@@ -16,24 +18,29 @@ pub struct Args {
 	pub log_level: LevelFilter,
 }
 
+/// Lines of the map shown before a parse error
+const CONTEXT_LINES: usize = 2;
+
 fn main() -> anyhow::Result<()> {
     // Parse command line arguments
 	let args = Args::parse();
-	println!("Args: {:?}", args);
 
 	// Setup logging
 	let _ = SimpleLogger::init(args.log_level, Default::default());
 
+    debug!("Args: {:?}", args);
     debug!("Loading map file: '{}'", args.file);
 
     // Load map file
-    let raw = std::fs::read_to_string(&args.file)?;
+    let raw = std::fs::read(&args.file)?;
 
     // Parse map
-    let m = match MapFile::parse(&raw) {
+    let m = match MapFile::parse_bytes(&raw) {
         Ok(v) => v,
-        // TODO: reshape errors to give -useful- context
-        Err(_e) => return Err(anyhow::anyhow!("Failed to parse .map")),
+        Err(e) => {
+            eprintln!("{}", render_error(&args.file, &raw, &e));
+            std::process::exit(1);
+        },
     };
 
     let i = m.info();
@@ -44,3 +51,67 @@ fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Render a parse error as an annotated snippet of the map, marking the failing column
+fn render_error(file: &str, raw: &[u8], e: &MapError) -> String {
+    let lines: Vec<_> = raw.split(|c| *c == b'\n' ).collect();
+    let first = e.line.saturating_sub(CONTEXT_LINES).max(1);
+    let w = e.line.to_string().len();
+
+    let mut s = String::new();
+
+    let _ = write!(s, "error: expected {}", e.expected);
+    if let Some(b) = &e.block {
+        let _ = write!(s, " in {}", b);
+    }
+    let _ = writeln!(s);
+    let _ = writeln!(s, "{:w$}--> {}:{}:{}", "", file, e.line, e.column, w = w);
+    let _ = writeln!(s, "{:w$} |", "", w = w);
+
+    // Preceding lines, for context
+    for n in first..e.line {
+        let l = lines.get(n - 1).copied().unwrap_or_default();
+        let l = String::from_utf8_lossy(l.strip_suffix(b"\r").unwrap_or(l));
+        let _ = writeln!(s, "{}", format!("{:>w$} | {}", n, l, w = w).trim_end());
+    }
+
+    // Failing line, with the caret aligned using the line's own tabs
+    let pad: String = e.text.chars().take(e.column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' } )
+        .collect();
+    let _ = writeln!(s, "{:>w$} | {}", e.line, e.text, w = w);
+    let _ = writeln!(s, "{:w$} | {}^ expected {}", "", pad, e.expected, w = w);
+    let _ = writeln!(s, "{:w$} |", "", w = w);
+    let _ = write!(s, "{:w$} = note: while parsing {}", "", e.context.join(" > "), w = w);
+
+    s
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn render_errors() {
+        let raw = "Memory Configuration\r
+\r
+Name             Origin             Length             Attributes\r
+FLASH            0x08000000         0x00100000         xr\r
+RAM              0xZZ\r
+";
+        let e = MapFile::parse(raw).unwrap_err();
+
+        assert_eq!(render_error("firmware.map", raw.as_bytes(), &e), "\
+error: expected hex in memory config
+ --> firmware.map:5:20
+  |
+3 | Name             Origin             Length             Attributes
+4 | FLASH            0x08000000         0x00100000         xr
+5 | RAM              0xZZ
+  |                    ^ expected hex
+  |
+  = note: while parsing map > memory config > memory > hex");
+    }
+}
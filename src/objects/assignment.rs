@@ -0,0 +1,260 @@
+use nom::{
+    IResult,
+    error::context, sequence::{tuple, delimited, terminated},
+    bytes::complete::{tag, take_while1},
+    character::complete::{line_ending, not_line_ending, space0, space1},
+    combinator::{map, map_opt, value}, branch::alt, multi::many0,
+};
+
+use nom_supreme::error::ErrorTree;
+
+use crate::{parse_hex, eol};
+use super::{Expr, BinaryOp, Name};
+
+/// Linker script symbol assignment (ie. `_estack = main_stack_base`, `PROVIDE (end = .)`)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Assignment<'a> {
+    pub symbol: Name<'a>,
+    /// Assignment operator (ie. `=`, `+=`)
+    pub op: &'a str,
+    /// Raw expression text
+    pub expr: Name<'a>,
+    /// Resulting value, where resolved by the linker
+    pub value: Option<u64>,
+    /// Assigned with `PROVIDE` or `PROVIDE_HIDDEN`
+    pub provide: bool,
+    /// Assigned with `HIDDEN` or `PROVIDE_HIDDEN`
+    pub hidden: bool,
+}
+
+impl <'a> Assignment<'a> {
+    /// Parse an assignment line (ie. `  0x0000000020030000  _estack = main_stack_base`)
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (_, _, value, _, mut a)) = context(
+            "assignment",
+            tuple((
+                many0(line_ending),
+                space1,
+                Self::parse_value,
+                space1,
+                terminated(Self::parse_statement, eol),
+            ))
+        )(s)?;
+
+        a.value = value;
+
+        Ok((o, a))
+    }
+
+    /// Parse the value column of an assignment line, unresolved assignments have no value
+    pub fn parse_value(s: &'a [u8]) -> IResult<&'a [u8], Option<u64>, ErrorTree<&'a [u8]>> {
+        alt((
+            map(parse_hex, Some),
+            value(None, alt((tag("[!provide]"), tag("*undef*")))),
+        ))(s)
+    }
+
+    /// Parse an assignment statement (ie. `_estack = main_stack_base`)
+    pub fn parse_statement(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        alt((
+            // Wrapped assignments (ie. `PROVIDE (end = .)`, or `PROVIDE (end, .)` in older versions)
+            map(
+                tuple((
+                    alt((
+                        value((true, true), tag("PROVIDE_HIDDEN")),
+                        value((true, false), tag("PROVIDE")),
+                        value((false, true), tag("HIDDEN")),
+                    )),
+                    delimited(space0, tag("("), space0),
+                    parse_name,
+                    alt((
+                        delimited(space1, parse_op, space1),
+                        value("=", terminated(tag(","), space0)),
+                    )),
+                    map_opt(not_line_ending, |v: &'a [u8]| v.trim_ascii_end().strip_suffix(b")") ),
+                )),
+                |((provide, hidden), _, symbol, _, expr)| Self{
+                    symbol, op: "=", expr: Name(expr.trim_ascii_end()), value: None, provide, hidden,
+                },
+            ),
+            // Plain assignments (ie. `_estack = main_stack_base`)
+            map(
+                tuple((
+                    parse_name,
+                    delimited(space1, parse_op, space1),
+                    not_line_ending,
+                )),
+                |(symbol, op, expr)| Self{
+                    symbol, op, expr: Name(expr.trim_ascii_end()), value: None, provide: false, hidden: false,
+                },
+            ),
+        ))(s)
+    }
+
+    /// Check whether this assigns the location counter (`.`)
+    pub fn is_location_counter(&self) -> bool {
+        self.symbol == "."
+    }
+
+    /// Check whether the linker applied this assignment, `PROVIDE` assignments are
+    /// skipped (`[!provide]`) where the symbol is defined elsewhere or never referenced
+    pub fn is_applied(&self) -> bool {
+        !(self.provide && self.value.is_none())
+    }
+
+    /// Parse the assigned expression, with compound operators (ie. `+=`)
+    /// expanded to a binary operation on the assigned symbol
+    pub fn expression(&self) -> Option<Expr<'a>> {
+        let e = Expr::parse_str(self.expr.to_str()?).ok()?;
+
+        let op = match self.op {
+            "=" => return Some(e),
+            "+=" => BinaryOp::Add,
+            "-=" => BinaryOp::Sub,
+            "*=" => BinaryOp::Mul,
+            "/=" => BinaryOp::Div,
+            "<<=" => BinaryOp::Shl,
+            ">>=" => BinaryOp::Shr,
+            "&=" => BinaryOp::And,
+            "|=" => BinaryOp::Or,
+            _ => return None,
+        };
+
+        Some(Expr::Binary(op, Box::new(Expr::Symbol(self.symbol.to_str()?)), Box::new(e)))
+    }
+
+    /// Fetch the alignment applied by a location counter move (ie. `. = ALIGN (0x4)`)
+    pub fn alignment(&self) -> Option<u64> {
+        if !self.is_location_counter() {
+            return None;
+        }
+
+        match self.expression()? {
+            Expr::Call("ALIGN", args) => match args.as_slice() {
+                [Expr::Number(n)] => Some(*n),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Parse an assigned symbol name
+fn parse_name(s: &[u8]) -> IResult<&[u8], Name<'_>, ErrorTree<&[u8]>> {
+    map(take_while1(|c: u8| !c.is_ascii_whitespace() && c != b'=' && c != b',' && c != b'(' && c != b')' ), Name)(s)
+}
+
+/// Parse an assignment operator
+fn parse_op(s: &[u8]) -> IResult<&[u8], &'static str, ErrorTree<&[u8]>> {
+    alt((
+        value("=", tag("=")), value("+=", tag("+=")), value("-=", tag("-=")),
+        value("*=", tag("*=")), value("/=", tag("/=")), value("<<=", tag("<<=")),
+        value(">>=", tag(">>=")), value("&=", tag("&=")), value("|=", tag("|=")),
+    ))(s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const ASSIGNMENTS: &[(Assignment, &str)] = &[
+        (
+            Assignment{
+                symbol: Name::new("main_stack_base"),
+                op: "=",
+                expr: Name::new("(ORIGIN (SRAM) + LENGTH (SRAM))"),
+                value: Some(0x0000000020030000),
+                provide: false,
+                hidden: false,
+            },
+            "                0x0000000020030000                main_stack_base = (ORIGIN (SRAM) + LENGTH (SRAM))",
+        ), (
+            Assignment{
+                symbol: Name::new("data_lma"),
+                op: "=",
+                expr: Name::new("LOADADDR (.data)"),
+                value: Some(0x00000000080fde08),
+                provide: false,
+                hidden: false,
+            },
+            " 0x00000000080fde08                data_lma = LOADADDR (.data)\n",
+        ), (
+            Assignment{
+                symbol: Name::new("end"),
+                op: "=",
+                expr: Name::new("."),
+                value: None,
+                provide: true,
+                hidden: false,
+            },
+            "                [!provide]                        PROVIDE (end = .)",
+        ), (
+            Assignment{
+                symbol: Name::new("__exidx_start"),
+                op: "=",
+                expr: Name::new("."),
+                value: Some(0x0000000008100000),
+                provide: true,
+                hidden: true,
+            },
+            "                0x0000000008100000                PROVIDE_HIDDEN (__exidx_start = .)",
+        ), (
+            Assignment{
+                symbol: Name::new("_end"),
+                op: "=",
+                expr: Name::new("."),
+                value: Some(0x0000000020003acc),
+                provide: true,
+                hidden: false,
+            },
+            "                0x0000000020003acc                PROVIDE (_end, .)",
+        ), (
+            Assignment{
+                symbol: Name::new("."),
+                op: "=",
+                expr: Name::new("ALIGN (0x4)"),
+                value: Some(0x0000000008040fac),
+                provide: false,
+                hidden: false,
+            },
+            "                0x0000000008040fac                . = ALIGN (0x4)",
+        ), (
+            Assignment{
+                symbol: Name::new("."),
+                op: "+=",
+                expr: Name::new("0x100"),
+                value: Some(0x0000000020000100),
+                provide: false,
+                hidden: false,
+            },
+            "                0x0000000020000100                . += 0x100",
+        ), (
+            Assignment{
+                symbol: Name::new("_stack_top"),
+                op: "=",
+                expr: Name::new("stack_base"),
+                value: None,
+                provide: false,
+                hidden: true,
+            },
+            "                *undef*                           HIDDEN (_stack_top = stack_base)",
+        ),
+    ];
+
+    #[test]
+    fn parse_assignments() {
+        for (v, raw) in ASSIGNMENTS {
+            let (_, p) = Assignment::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+        }
+    }
+
+    #[test]
+    fn reject_symbols() {
+        // Plain and demangled symbols are not assignments
+        assert!(Assignment::parse(b"                0x0000000008042108                norcow_set").is_err());
+        assert!(Assignment::parse(b"                0x0000000008042108                Foo::operator=(Foo const&)").is_err());
+    }
+}
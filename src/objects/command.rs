@@ -0,0 +1,187 @@
+use nom::{
+    IResult,
+    error::context, sequence::{tuple, delimited},
+    bytes::complete::{tag, is_not},
+    character::complete::{space0, space1, not_line_ending},
+    combinator::{map, map_res, map_opt, value}, branch::alt,
+};
+
+use nom_supreme::error::ErrorTree;
+
+use crate::eol;
+use super::{FileInfo, InputFile, Name, FilePath};
+
+/// Linker command from the memory map (ie. `LOAD`, `START GROUP`, `OUTPUT(...)`)
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command<'a> {
+    /// Input file loaded (`LOAD build/main.o`)
+    Load(InputFile<'a>),
+    /// Linker generated stubs loaded (`LOAD linker stubs`)
+    LoadStubs,
+    /// Start of a link group (`START GROUP`)
+    StartGroup,
+    /// End of a link group (`END GROUP`)
+    EndGroup,
+    /// Output file and BFD target (`OUTPUT(build/firmware.elf elf32-littlearm)`)
+    Output{
+        file: FilePath<'a>,
+        target: Option<BfdTarget<'a>>,
+    },
+    /// Default output format (`OUTPUT_FORMAT(elf32-littlearm)`)
+    OutputFormat(BfdTarget<'a>),
+    /// Default input format (`TARGET(binary)`)
+    Target(BfdTarget<'a>),
+}
+
+impl <'a> Command<'a> {
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        context(
+            "command",
+            alt((
+                value(Command::LoadStubs, tuple((tag("LOAD"), space1, tag("linker stubs"), eol))),
+                map(tuple((FileInfo::parse, eol)), |(f, _)| Command::Load(f.name) ),
+                value(Command::StartGroup, tuple((tag("START GROUP"), eol))),
+                value(Command::EndGroup, tuple((tag("END GROUP"), eol))),
+                map(
+                    tuple((
+                        tag("OUTPUT("),
+                        map_opt(not_line_ending, Self::split_output),
+                        eol,
+                    )),
+                    |(_, (file, target), _)| Command::Output{ file, target },
+                ),
+                map(
+                    tuple((tag("OUTPUT_FORMAT"), Self::parse_args, eol)),
+                    |(_, v, _)| Command::OutputFormat(BfdTarget(v)),
+                ),
+                map(
+                    tuple((tag("TARGET"), Self::parse_args, eol)),
+                    |(_, v, _)| Command::Target(BfdTarget(v)),
+                ),
+            ))
+        )(s)
+    }
+
+    /// Split `OUTPUT(...)` arguments into the output file and target, the file
+    /// path may contain spaces and parentheses while the target may not
+    fn split_output(s: &'a [u8]) -> Option<(FilePath<'a>, Option<BfdTarget<'a>>)> {
+        let s = s.trim_ascii_end().strip_suffix(b")")?;
+
+        let (file, target) = match Name(s).rsplit_once(b' ') {
+            Some((file, target)) if !file.is_empty() => (file, Some(BfdTarget(target.to_str()?))),
+            _ => (Name(s), None),
+        };
+
+        Some((FilePath::from_name(Name(file.as_bytes().trim_ascii_end())), target))
+            .filter(|(f, _)| !f.as_name().is_empty() )
+    }
+
+    /// Parse bracketed arguments, returning the first (default) argument
+    fn parse_args(s: &'a [u8]) -> IResult<&'a [u8], &'a str, ErrorTree<&'a [u8]>> {
+        map(
+            delimited(
+                tuple((space0, tag("("), space0)),
+                map_res(is_not(")\r\n"), std::str::from_utf8),
+                tag(")"),
+            ),
+            |v: &str| v.split(',').next().unwrap_or(v).trim().trim_matches('"'),
+        )(s)
+    }
+}
+
+/// BFD target name (ie. `elf32-littlearm`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BfdTarget<'a>(pub &'a str);
+
+/// Target byte order
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl <'a> BfdTarget<'a> {
+    /// Fetch the target address width in bits (ie. 32 for `elf32-*`)
+    pub fn bits(&self) -> Option<u32> {
+        let (format, _) = self.0.split_once('-')?;
+        let digits = format.trim_start_matches(|c: char| !c.is_ascii_digit() );
+        digits.parse().ok()
+    }
+
+    /// Fetch the target byte order, where known
+    pub fn endian(&self) -> Option<Endian> {
+        let arch = self.arch_name();
+        if arch.starts_with("little") || arch.starts_with("tradlittle") || arch.ends_with("-le") {
+            Some(Endian::Little)
+        } else if arch.starts_with("big") || arch.starts_with("tradbig") || arch.ends_with("-be") {
+            Some(Endian::Big)
+        } else {
+            match arch {
+                "i386" | "x86-64" | "avr" | "msp430" => Some(Endian::Little),
+                "powerpc" | "m68k" | "sparc" => Some(Endian::Big),
+                _ => None,
+            }
+        }
+    }
+
+    /// Fetch the target architecture (ie. `arm` for `elf32-littlearm`)
+    pub fn arch(&self) -> &'a str {
+        let arch = self.arch_name();
+        ["tradlittle", "tradbig", "little", "big"].iter()
+            .find_map(|p| arch.strip_prefix(p) )
+            .unwrap_or(arch)
+    }
+
+    /// Fetch the target name following the format prefix (ie. `littlearm`)
+    fn arch_name(&self) -> &'a str {
+        self.0.split_once('-').map(|(_, a)| a ).unwrap_or(self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const COMMANDS: &[(Command, &str)] = &[
+        (Command::Load(InputFile::Path(FilePath::new("build/firmware/vendor/trezor-storage/norcow.o"))), "LOAD build/firmware/vendor/trezor-storage/norcow.o"),
+        (Command::LoadStubs, "LOAD linker stubs"),
+        (Command::StartGroup, "START GROUP"),
+        (Command::EndGroup, "END GROUP\n"),
+        (
+            Command::Output{ file: FilePath::new("build/firmware/firmware.elf"), target: Some(BfdTarget("elf32-littlearm")) },
+            "OUTPUT(build/firmware/firmware.elf elf32-littlearm)",
+        ),
+        (Command::Output{ file: FilePath::new("a.out"), target: None }, "OUTPUT(a.out)"),
+        (
+            Command::Output{ file: FilePath::new(r"C:\My Project (v2)\build\firmware.elf"), target: Some(BfdTarget("elf32-littlearm")) },
+            r"OUTPUT(C:\My Project (v2)\build\firmware.elf elf32-littlearm)",
+        ),
+        (Command::OutputFormat(BfdTarget("elf32-littlearm")), r#"OUTPUT_FORMAT("elf32-littlearm", "elf32-bigarm", "elf32-littlearm")"#),
+        (Command::Target(BfdTarget("binary")), "TARGET(binary)"),
+    ];
+
+    #[test]
+    fn parse_commands() {
+        for (v, raw) in COMMANDS {
+            let (_, p) = Command::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+        }
+    }
+
+    #[test]
+    fn bfd_targets() {
+        let t = BfdTarget("elf32-littlearm");
+        assert_eq!((t.bits(), t.endian(), t.arch()), (Some(32), Some(Endian::Little), "arm"));
+
+        let t = BfdTarget("elf64-x86-64");
+        assert_eq!((t.bits(), t.endian(), t.arch()), (Some(64), Some(Endian::Little), "x86-64"));
+
+        let t = BfdTarget("elf32-tradbigmips");
+        assert_eq!((t.bits(), t.endian(), t.arch()), (Some(32), Some(Endian::Big), "mips"));
+
+        let t = BfdTarget("binary");
+        assert_eq!((t.bits(), t.endian(), t.arch()), (None, None, "binary"));
+    }
+}
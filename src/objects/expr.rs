@@ -0,0 +1,501 @@
+use std::fmt;
+
+use nom::{
+    IResult,
+    error::context, sequence::{tuple, delimited, preceded, terminated},
+    bytes::complete::{tag, take_while1, is_not},
+    character::complete::{space0, digit1, hex_digit1, one_of},
+    combinator::{map, map_res, opt, value, not, all_consuming}, branch::alt,
+    multi::{many0, separated_list0},
+};
+
+use nom_supreme::error::ErrorTree;
+
+use crate::MapFile;
+use super::Name;
+
+/// Linker script expression (ie. `(ORIGIN (SRAM) + LENGTH (SRAM))`)
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expr<'a> {
+    /// Numeric constant
+    Number(u64),
+    /// Symbol (or location counter `.`) reference
+    Symbol(&'a str),
+    /// Unary operation (ie. `~x`)
+    Unary(UnaryOp, Box<Expr<'a>>),
+    /// Binary operation (ie. `a + b`)
+    Binary(BinaryOp, Box<Expr<'a>>, Box<Expr<'a>>),
+    /// Conditional (`a ? b : c`)
+    Conditional(Box<Expr<'a>>, Box<Expr<'a>>, Box<Expr<'a>>),
+    /// Builtin function call (ie. `ORIGIN (SRAM)`)
+    Call(&'a str, Vec<Expr<'a>>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+    Invert,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BinaryOp {
+    Mul, Div, Rem,
+    Add, Sub,
+    Shl, Shr,
+    Lt, Le, Gt, Ge,
+    Eq, Ne,
+    And, Xor, Or,
+    LogicalAnd, LogicalOr,
+}
+
+/// Map objects an expression depends on
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Dependency<'a> {
+    /// Memory region (ie. via `ORIGIN` / `LENGTH`)
+    Region(&'a str),
+    /// Output section (ie. via `ADDR` / `SIZEOF`)
+    Section(&'a str),
+    /// Other symbol
+    Symbol(&'a str),
+    /// Location counter (`.`)
+    LocationCounter,
+}
+
+/// Errors evaluating expressions against a map
+#[derive(Clone, PartialEq, Debug)]
+pub enum EvalError<'a> {
+    /// Expression could not be parsed
+    Syntax(Name<'a>),
+    UnknownSymbol(&'a str),
+    UnknownRegion(&'a str),
+    UnknownSection(&'a str),
+    /// Location counter value is not available
+    LocationCounter,
+    /// Function is not supported or cannot be evaluated from the map
+    Unsupported(&'a str),
+    DivideByZero,
+}
+
+impl <'a> fmt::Display for EvalError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Syntax(v) => write!(f, "invalid expression '{}'", v),
+            EvalError::UnknownSymbol(v) => write!(f, "unknown symbol '{}'", v),
+            EvalError::UnknownRegion(v) => write!(f, "unknown memory region '{}'", v),
+            EvalError::UnknownSection(v) => write!(f, "unknown section '{}'", v),
+            EvalError::LocationCounter => write!(f, "location counter unavailable"),
+            EvalError::Unsupported(v) => write!(f, "unsupported function '{}'", v),
+            EvalError::DivideByZero => write!(f, "divide by zero"),
+        }
+    }
+}
+
+impl <'a> Expr<'a> {
+    /// Parse a complete expression string
+    pub fn parse_str(s: &'a str) -> Result<Self, nom::Err<ErrorTree<&'a str>>> {
+        all_consuming(delimited(space0, Self::parse, space0))(s).map(|(_, e)| e )
+    }
+
+    pub fn parse(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
+        context("expression", parse_conditional)(s)
+    }
+
+    /// Evaluate this expression against the provided map, with an optional location counter value
+    pub fn evaluate(&self, map: &MapFile<'a>, dot: Option<u64>) -> Result<u64, EvalError<'a>> {
+        let v = match self {
+            Expr::Number(v) => *v,
+            Expr::Symbol(".") => dot.ok_or(EvalError::LocationCounter)?,
+            Expr::Symbol(s) => map.symbol_value(s).ok_or(EvalError::UnknownSymbol(s))?,
+            Expr::Unary(op, a) => {
+                let a = a.evaluate(map, dot)?;
+                match op {
+                    UnaryOp::Neg => a.wrapping_neg(),
+                    UnaryOp::Not => (a == 0) as u64,
+                    UnaryOp::Invert => !a,
+                }
+            },
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.evaluate(map, dot)?, b.evaluate(map, dot)?);
+                match op {
+                    BinaryOp::Mul => a.wrapping_mul(b),
+                    BinaryOp::Div => a.checked_div(b).ok_or(EvalError::DivideByZero)?,
+                    BinaryOp::Rem => a.checked_rem(b).ok_or(EvalError::DivideByZero)?,
+                    BinaryOp::Add => a.wrapping_add(b),
+                    BinaryOp::Sub => a.wrapping_sub(b),
+                    BinaryOp::Shl => a.checked_shl(b as u32).unwrap_or(0),
+                    BinaryOp::Shr => a.checked_shr(b as u32).unwrap_or(0),
+                    BinaryOp::Lt => (a < b) as u64,
+                    BinaryOp::Le => (a <= b) as u64,
+                    BinaryOp::Gt => (a > b) as u64,
+                    BinaryOp::Ge => (a >= b) as u64,
+                    BinaryOp::Eq => (a == b) as u64,
+                    BinaryOp::Ne => (a != b) as u64,
+                    BinaryOp::And => a & b,
+                    BinaryOp::Xor => a ^ b,
+                    BinaryOp::Or => a | b,
+                    BinaryOp::LogicalAnd => (a != 0 && b != 0) as u64,
+                    BinaryOp::LogicalOr => (a != 0 || b != 0) as u64,
+                }
+            },
+            Expr::Conditional(c, a, b) => match c.evaluate(map, dot)? {
+                0 => b.evaluate(map, dot)?,
+                _ => a.evaluate(map, dot)?,
+            },
+            Expr::Call(f, args) => Self::evaluate_call(f, args, map, dot)?,
+        };
+
+        Ok(v)
+    }
+
+    fn evaluate_call(f: &'a str, args: &[Expr<'a>], map: &MapFile<'a>, dot: Option<u64>) -> Result<u64, EvalError<'a>> {
+        let v = match (f, args) {
+            ("ORIGIN", [Expr::Symbol(r)]) => map.memory.iter().find(|m| m.name == *r ).ok_or(EvalError::UnknownRegion(r))?.origin,
+            ("LENGTH", [Expr::Symbol(r)]) => map.memory.iter().find(|m| m.name == *r ).ok_or(EvalError::UnknownRegion(r))?.length,
+            ("ADDR", [Expr::Symbol(s)]) => map.section(s).and_then(|s| s.addr ).ok_or(EvalError::UnknownSection(s))?,
+            ("LOADADDR", [Expr::Symbol(s)]) => map.section(s).and_then(|s| s.lma() ).ok_or(EvalError::UnknownSection(s))?,
+            ("SIZEOF", [Expr::Symbol(s)]) => map.section(s).and_then(|s| s.size ).ok_or(EvalError::UnknownSection(s))?,
+            ("DEFINED", [Expr::Symbol(s)]) => map.symbol_value(s).is_some() as u64,
+            ("ALIGN" | "NEXT" | "BLOCK", [a]) => align(dot.ok_or(EvalError::LocationCounter)?, a.evaluate(map, dot)?),
+            ("ALIGN", [v, a]) => align(v.evaluate(map, dot)?, a.evaluate(map, dot)?),
+            ("ABSOLUTE", [v]) => v.evaluate(map, dot)?,
+            ("MAX", [a, b]) => a.evaluate(map, dot)?.max(b.evaluate(map, dot)?),
+            ("MIN", [a, b]) => a.evaluate(map, dot)?.min(b.evaluate(map, dot)?),
+            ("LOG2CEIL", [v]) => match v.evaluate(map, dot)? {
+                0 | 1 => 0,
+                v => 64 - (v - 1).leading_zeros() as u64,
+            },
+            ("SEGMENT_START", [_, v]) => v.evaluate(map, dot)?,
+            _ => return Err(EvalError::Unsupported(f)),
+        };
+
+        Ok(v)
+    }
+
+    /// Fetch the regions, sections and symbols this expression directly depends on
+    pub fn dependencies(&self) -> Vec<Dependency<'a>> {
+        let mut deps = vec![];
+        self.collect_dependencies(&mut deps);
+        deps
+    }
+
+    fn collect_dependencies(&self, deps: &mut Vec<Dependency<'a>>) {
+        let d = match self {
+            Expr::Number(_) => return,
+            Expr::Symbol(".") => Dependency::LocationCounter,
+            Expr::Symbol(s) => Dependency::Symbol(s),
+            Expr::Unary(_, a) => return a.collect_dependencies(deps),
+            Expr::Binary(_, a, b) => {
+                a.collect_dependencies(deps);
+                return b.collect_dependencies(deps);
+            },
+            Expr::Conditional(c, a, b) => {
+                c.collect_dependencies(deps);
+                a.collect_dependencies(deps);
+                return b.collect_dependencies(deps);
+            },
+            Expr::Call("ORIGIN" | "LENGTH", args) => match args.as_slice() {
+                [Expr::Symbol(r)] => Dependency::Region(r),
+                _ => return,
+            },
+            Expr::Call("ADDR" | "LOADADDR" | "SIZEOF", args) => match args.as_slice() {
+                [Expr::Symbol(s)] => Dependency::Section(s),
+                _ => return,
+            },
+            Expr::Call("ALIGN" | "NEXT" | "BLOCK", args) if args.len() == 1 => {
+                deps.push(Dependency::LocationCounter);
+                return args[0].collect_dependencies(deps);
+            },
+            Expr::Call("DEFINED", _) => return,
+            Expr::Call(_, args) => {
+                for a in args {
+                    a.collect_dependencies(deps);
+                }
+                return;
+            },
+        };
+
+        if !deps.contains(&d) {
+            deps.push(d);
+        }
+    }
+}
+
+/// Align a value up to the provided alignment
+fn align(v: u64, a: u64) -> u64 {
+    match a {
+        0 => v,
+        a => v.wrapping_add(a - 1) / a * a,
+    }
+}
+
+/// Build a left-associative binary operator level
+fn binary_level<'a>(
+    ops: fn(&'a str) -> IResult<&'a str, BinaryOp, ErrorTree<&'a str>>,
+    next: fn(&'a str) -> IResult<&'a str, Expr<'a>, ErrorTree<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Expr<'a>, ErrorTree<&'a str>> {
+    move |s| {
+        let (o, (first, rest)) = tuple((
+            next,
+            many0(tuple((delimited(space0, ops, space0), next))),
+        ))(s)?;
+
+        let e = rest.into_iter().fold(first, |a, (op, b)| Expr::Binary(op, Box::new(a), Box::new(b)) );
+
+        Ok((o, e))
+    }
+}
+
+fn parse_conditional(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    let (o, (c, branches)) = tuple((
+        parse_logical_or,
+        opt(tuple((
+            preceded(tuple((space0, tag("?"), space0)), parse_conditional),
+            preceded(tuple((space0, tag(":"), space0)), parse_conditional),
+        ))),
+    ))(s)?;
+
+    let e = match branches {
+        Some((a, b)) => Expr::Conditional(Box::new(c), Box::new(a), Box::new(b)),
+        None => c,
+    };
+
+    Ok((o, e))
+}
+
+fn parse_logical_or(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| value(BinaryOp::LogicalOr, tag("||"))(s), parse_logical_and)(s)
+}
+
+fn parse_logical_and(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| value(BinaryOp::LogicalAnd, tag("&&"))(s), parse_or)(s)
+}
+
+fn parse_or(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| value(BinaryOp::Or, terminated(tag("|"), not(tag("|"))))(s), parse_xor)(s)
+}
+
+fn parse_xor(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| value(BinaryOp::Xor, tag("^"))(s), parse_and)(s)
+}
+
+fn parse_and(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| value(BinaryOp::And, terminated(tag("&"), not(tag("&"))))(s), parse_equality)(s)
+}
+
+fn parse_equality(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| alt((
+        value(BinaryOp::Eq, tag("==")),
+        value(BinaryOp::Ne, tag("!=")),
+    ))(s), parse_relational)(s)
+}
+
+fn parse_relational(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| alt((
+        value(BinaryOp::Le, tag("<=")),
+        value(BinaryOp::Ge, tag(">=")),
+        value(BinaryOp::Lt, terminated(tag("<"), not(tag("<")))),
+        value(BinaryOp::Gt, terminated(tag(">"), not(tag(">")))),
+    ))(s), parse_shift)(s)
+}
+
+fn parse_shift(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| alt((
+        value(BinaryOp::Shl, tag("<<")),
+        value(BinaryOp::Shr, tag(">>")),
+    ))(s), parse_additive)(s)
+}
+
+fn parse_additive(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| alt((
+        value(BinaryOp::Add, tag("+")),
+        value(BinaryOp::Sub, tag("-")),
+    ))(s), parse_multiplicative)(s)
+}
+
+fn parse_multiplicative(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    binary_level(|s| alt((
+        value(BinaryOp::Mul, tag("*")),
+        value(BinaryOp::Div, tag("/")),
+        value(BinaryOp::Rem, tag("%")),
+    ))(s), parse_unary)(s)
+}
+
+fn parse_unary(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    alt((
+        map(
+            tuple((
+                alt((
+                    value(UnaryOp::Neg, tag("-")),
+                    value(UnaryOp::Not, tag("!")),
+                    value(UnaryOp::Invert, tag("~")),
+                )),
+                preceded(space0, parse_unary),
+            )),
+            |(op, e)| Expr::Unary(op, Box::new(e)),
+        ),
+        parse_primary,
+    ))(s)
+}
+
+fn parse_primary(s: &str) -> IResult<&str, Expr<'_>, ErrorTree<&str>> {
+    alt((
+        // Bracketed expression
+        delimited(
+            tuple((tag("("), space0)),
+            parse_conditional,
+            tuple((space0, tag(")"))),
+        ),
+        map(parse_number, Expr::Number),
+        // Function call (ie. `ORIGIN (SRAM)`)
+        map(
+            tuple((
+                take_while1(|c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_' ),
+                delimited(
+                    tuple((space0, tag("("), space0)),
+                    separated_list0(tuple((space0, tag(","), space0)), parse_conditional),
+                    tuple((space0, tag(")"))),
+                ),
+            )),
+            |(f, args)| Expr::Call(f, args),
+        ),
+        map(parse_name, Expr::Symbol),
+    ))(s)
+}
+
+/// Parse a numeric constant (ie. `0x4`, `16`, `4K`)
+fn parse_number(s: &str) -> IResult<&str, u64, ErrorTree<&str>> {
+    let (o, (v, suffix)) = tuple((
+        alt((
+            map_res(preceded(alt((tag("0x"), tag("0X"))), hex_digit1), |v| u64::from_str_radix(v, 16) ),
+            map_res(digit1, |v: &str| v.parse::<u64>() ),
+        )),
+        opt(one_of("KM")),
+    ))(s)?;
+
+    let v = match suffix {
+        Some('K') => v << 10,
+        Some('M') => v << 20,
+        _ => v,
+    };
+
+    Ok((o, v))
+}
+
+/// Parse a symbol, section or region name (optionally quoted)
+fn parse_name(s: &str) -> IResult<&str, &str, ErrorTree<&str>> {
+    alt((
+        delimited(tag("\""), is_not("\""), tag("\"")),
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$' ),
+    ))(s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn call<'a>(f: &'a str, args: Vec<Expr<'a>>) -> Expr<'a> {
+        Expr::Call(f, args)
+    }
+
+    fn binary<'a>(op: BinaryOp, a: Expr<'a>, b: Expr<'a>) -> Expr<'a> {
+        Expr::Binary(op, Box::new(a), Box::new(b))
+    }
+
+    #[test]
+    fn parse_expressions() {
+        let exprs = vec![
+            (
+                binary(
+                    BinaryOp::Add,
+                    call("ORIGIN", vec![Expr::Symbol("SRAM")]),
+                    call("LENGTH", vec![Expr::Symbol("SRAM")]),
+                ),
+                "(ORIGIN (SRAM) + LENGTH (SRAM))",
+            ),
+            (call("LOADADDR", vec![Expr::Symbol(".data")]), "LOADADDR (.data)"),
+            (call("ALIGN", vec![Expr::Number(4)]), "ALIGN (0x4)"),
+            (Expr::Symbol("main_stack_base"), "main_stack_base"),
+            (Expr::Symbol("."), "."),
+            (
+                binary(
+                    BinaryOp::And,
+                    binary(BinaryOp::Add, Expr::Symbol("."), Expr::Number(3)),
+                    Expr::Unary(UnaryOp::Invert, Box::new(Expr::Number(3))),
+                ),
+                "((. + 0x3) & ~ (0x3))",
+            ),
+            (
+                binary(
+                    BinaryOp::Add,
+                    Expr::Number(1),
+                    binary(BinaryOp::Mul, Expr::Number(2), Expr::Number(3)),
+                ),
+                "1 + 2 * 3",
+            ),
+            (
+                Expr::Conditional(
+                    Box::new(call("DEFINED", vec![Expr::Symbol("__stack_size__")])),
+                    Box::new(Expr::Symbol("__stack_size__")),
+                    Box::new(Expr::Number(0x400)),
+                ),
+                "DEFINED (__stack_size__) ? __stack_size__ : 1K",
+            ),
+            (
+                binary(
+                    BinaryOp::Le,
+                    Expr::Symbol("_edata"),
+                    binary(BinaryOp::Shl, Expr::Number(1), Expr::Number(4)),
+                ),
+                "(_edata <= (0x1 << 0x4))",
+            ),
+        ];
+
+        for (v, raw) in exprs {
+            let p = Expr::parse_str(raw).unwrap();
+            assert_eq!(p, v, "{}", raw);
+        }
+    }
+
+    #[test]
+    fn expression_dependencies() {
+        let e = Expr::parse_str("(((LENGTH (FLASH) - SIZEOF (.vendorheader)) - SIZEOF (.header)) + _extra)").unwrap();
+
+        assert_eq!(e.dependencies(), &[
+            Dependency::Region("FLASH"),
+            Dependency::Section(".vendorheader"),
+            Dependency::Section(".header"),
+            Dependency::Symbol("_extra"),
+        ]);
+
+        let e = Expr::parse_str("ALIGN (0x4)").unwrap();
+        assert_eq!(e.dependencies(), &[Dependency::LocationCounter]);
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        let m = MapFile::default();
+
+        let exprs = &[
+            ("((0x10 + 0x3) & ~ (0x3))", Ok(0x10)),
+            ("(0x8 - 0x10)", Ok(0xfffffffffffffff8)),
+            ("MAX (0x4, 0x8)", Ok(0x8)),
+            ("ALIGN (0x1001, 0x100)", Ok(0x1100)),
+            ("LOG2CEIL (0x100)", Ok(8)),
+            ("(0x1 / 0x0)", Err(EvalError::DivideByZero)),
+            ("ALIGN (0x4)", Err(EvalError::LocationCounter)),
+            ("ORIGIN (SRAM)", Err(EvalError::UnknownRegion("SRAM"))),
+            ("missing", Err(EvalError::UnknownSymbol("missing"))),
+        ];
+
+        for (raw, v) in exprs {
+            let e = Expr::parse_str(raw).unwrap();
+            assert_eq!(&e.evaluate(&m, None), v, "{}", raw);
+        }
+
+        let e = Expr::parse_str("ALIGN (0x4)").unwrap();
+        assert_eq!(e.evaluate(&m, Some(0x0000000008040fab)), Ok(0x0000000008040fac));
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+
+use nom::{
+    IResult,
+    error::context,
+    bytes::complete::tag,
+    character::complete::not_line_ending,
+    combinator::{map_opt, value}, branch::alt,
+};
+
+use nom_supreme::error::ErrorTree;
+
+use super::{Name, FilePath};
+
+/// Input file reference (ie. `build/main.o`, `libtrezor_lib.a(core-1.o)`)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum InputFile<'a> {
+    /// Plain file path, an object or an archive loaded as a whole (ie. `build/main.o`)
+    Path(FilePath<'a>),
+    /// Archive member (ie. `libc.a(memcpy.o)`)
+    Member{
+        archive: FilePath<'a>,
+        member: Name<'a>,
+    },
+    /// Linker generated input (ie. `linker stubs`)
+    Linker(Name<'a>),
+}
+
+impl <'a> InputFile<'a> {
+    /// Parse an input file column, paths extend to the end of the line
+    /// and may contain spaces (ie. `C:\My Project\main.o`)
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        context(
+            "input file",
+            alt((
+                value(InputFile::Linker(Name::new("linker stubs")), tag("linker stubs")),
+                map_opt(not_line_ending, |v: &'a [u8]| {
+                    Some(Name(v.trim_ascii_end())).filter(|v| !v.is_empty() ).map(InputFile::from_path)
+                }),
+            ))
+        )(s)
+    }
+
+    /// Split a file path into an archive and member, where applicable,
+    /// removing enclosing quotes
+    pub fn from_path(s: Name<'a>) -> Self {
+        let s = FilePath::from_name(s).as_name();
+
+        // Archive paths may also contain parentheses (ie. `C:\Program Files (x86)\libc.a(memcpy.o)`)
+        let member = s.as_bytes().strip_suffix(b")")
+            .and_then(|v| Name(v).rsplit_once(b'(') )
+            .filter(|(archive, member)| !archive.is_empty() && !member.is_empty() );
+
+        match member {
+            Some((archive, member)) => InputFile::Member{ archive: FilePath::from_name(archive), member },
+            None => InputFile::Path(FilePath(s)),
+        }
+    }
+
+    /// Fetch the file path, the containing archive for archive members
+    pub fn path(&self) -> FilePath<'a> {
+        match self {
+            InputFile::Path(p) => *p,
+            InputFile::Member{ archive, .. } => *archive,
+            InputFile::Linker(n) => FilePath(*n),
+        }
+    }
+
+    /// Fetch the containing archive, for archive members
+    pub fn archive(&self) -> Option<FilePath<'a>> {
+        match self {
+            InputFile::Member{ archive, .. } => Some(*archive),
+            _ => None,
+        }
+    }
+
+    /// Fetch the object name within the containing archive, for archive members
+    pub fn member(&self) -> Option<Name<'a>> {
+        match self {
+            InputFile::Member{ member, .. } => Some(*member),
+            _ => None,
+        }
+    }
+
+    /// Check whether this input was generated by the linker
+    pub fn is_linker(&self) -> bool {
+        matches!(self, InputFile::Linker(_))
+    }
+}
+
+impl <'a> fmt::Display for InputFile<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputFile::Path(p) => write!(f, "{}", p),
+            InputFile::Member{ archive, member } => write!(f, "{}({})", archive, member),
+            InputFile::Linker(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    const FILES: &[(InputFile, &str)] = &[
+        (InputFile::Path(FilePath::new("build/firmware/embed/firmware/main.o")), "build/firmware/embed/firmware/main.o"),
+        (InputFile::Path(FilePath::new("/usr/lib/arm-none-eabi/lib/libc.a")), "/usr/lib/arm-none-eabi/lib/libc.a"),
+        (
+            InputFile::Member{
+                archive: FilePath::new("build/libtrezor_lib.a"),
+                member: Name::new("compiler_builtins-c5e8bf4bcd3a1b8e.compiler_builtins.6c3a4a8b-cgu.0.rcgu.o"),
+            },
+            "build/libtrezor_lib.a(compiler_builtins-c5e8bf4bcd3a1b8e.compiler_builtins.6c3a4a8b-cgu.0.rcgu.o)",
+        ),
+        (InputFile::Linker(Name::new("linker stubs")), "linker stubs"),
+        (
+            InputFile::Path(FilePath::new(r"C:\Users\Jo Bloggs\My Project\build\main.o")),
+            r"C:\Users\Jo Bloggs\My Project\build\main.o",
+        ),
+        (
+            InputFile::Member{
+                archive: FilePath::new(r"C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a"),
+                member: Name::new("lib_a-memcpy.o"),
+            },
+            r"C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a(lib_a-memcpy.o)",
+        ),
+    ];
+
+    #[test]
+    fn parse_input_files() {
+        for (v, raw) in FILES {
+            let (o, p) = InputFile::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+            assert!(o.is_empty());
+            assert_eq!(&p.to_string(), raw);
+        }
+
+        let m = InputFile::from_path(Name::new("libc.a(memcpy.o)"));
+        assert_eq!(m.path(), "libc.a");
+        assert_eq!((m.archive(), m.member()), (Some(FilePath::new("libc.a")), Some(Name::new("memcpy.o"))));
+
+        // Quoted paths are unquoted
+        let (_, p) = InputFile::parse(b"\"build dir/main.o\"  \n").unwrap();
+        assert_eq!(p, InputFile::Path(FilePath::new("build dir/main.o")));
+    }
+}
@@ -1,29 +1,34 @@
+use std::fmt;
+
 use nom::{
     IResult,
     error::context, sequence::{tuple, delimited, terminated, preceded}, 
-    bytes::complete::{take_while1, tag, is_not, take_while}, 
-    character::complete::{line_ending, space1, space0},
-    combinator::{map_res, opt}, multi::many0,
+    bytes::complete::{take_while1, tag}, 
+    character::complete::{line_ending, not_line_ending, space1, space0},
+    combinator::{map, map_opt, opt, not, eof, recognize}, multi::{many0, many1}, branch::alt,
 };
 
 use nom_supreme::error::ErrorTree;
 
-use crate::{parse_path, from_hex, is_hex_digit};
+use crate::{parse_heading, parse_path, parse_hex, parse_column, parse_column_sep, parse_pre_relax_size, parse_items, is_block_end, eol};
+use super::{InputFile, Name, FilePath, section::{is_debug_section, CODE_SECTIONS, ZERO_INIT_SECTIONS}};
 
 /// File used in linking operation
 #[derive(Clone, PartialEq, Debug)]
 pub struct FileInfo<'a> {
-    pub name: &'a str,
+    pub name: InputFile<'a>,
+    /// Index of the link group (`START GROUP` ... `END GROUP`) containing this file
+    pub group: Option<usize>,
 }
 
 impl <'a> FileInfo<'a> {
-    pub fn parse(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
         let (o, r) = context(
             "load",
             tuple((
                 tag("LOAD"),
                 space1,
-                parse_path,
+                InputFile::parse,
             ))
         )(s)?;
 
@@ -31,6 +36,7 @@ impl <'a> FileInfo<'a> {
             o,
             Self{
                 name: r.2,
+                group: None,
             },
         ))
     }
@@ -39,15 +45,126 @@ impl <'a> FileInfo<'a> {
 /// Available memories (from linker file)
 #[derive(Clone, PartialEq, Debug)]
 pub struct MemoryInfo<'a> {
-    pub name: &'a str,
+    pub name: Name<'a>,
     pub origin: u64,
     pub length: u64,
-    pub attrs: Option<&'a str>,
+    pub attrs: Option<MemoryAttrs>,
+}
+
+/// Memory region attributes (ie. `rx`, `rx !w`)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MemoryAttrs {
+    /// Flags for sections placed in this region
+    pub flags: MemoryFlags,
+    /// Flags for sections excluded from this region (following `!`)
+    pub negated: MemoryFlags,
+}
+
+/// Memory region or section flags
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct MemoryFlags {
+    /// Read-only (`r`)
+    pub read: bool,
+    /// Read / write (`w`)
+    pub write: bool,
+    /// Executable (`x`)
+    pub execute: bool,
+    /// Allocatable (`a`)
+    pub alloc: bool,
+    /// Initialised (`i` or `l`)
+    pub init: bool,
+}
+
+impl MemoryAttrs {
+    /// Parse attributes (ie. `rx!w`), returning `None` for unknown flags
+    pub fn parse(s: &[u8]) -> Option<Self> {
+        let mut attrs = Self::default();
+        let mut flags = &mut attrs.flags;
+
+        for c in s {
+            match c.to_ascii_lowercase() {
+                b'r' => flags.read = true,
+                b'w' => flags.write = true,
+                b'x' => flags.execute = true,
+                b'a' => flags.alloc = true,
+                b'i' | b'l' => flags.init = true,
+                b'!' => flags = &mut attrs.negated,
+                b' ' => (),
+                _ => return None,
+            }
+        }
+
+        Some(attrs)
+    }
+
+    /// Check whether sections placed in this region may be executed
+    pub fn is_executable(&self) -> bool {
+        self.flags.execute && !self.negated.execute
+    }
+
+    /// Check whether sections placed in this region may be written
+    pub fn is_writable(&self) -> bool {
+        self.flags.write && !self.negated.write
+    }
+
+    /// Check whether a section with the provided flags is placed in this region by default,
+    /// matching any region flag and no negated flag
+    pub fn accepts(&self, section: MemoryFlags) -> bool {
+        self.flags.intersects(&section) && !self.negated.intersects(&section)
+    }
+}
+
+impl MemoryFlags {
+    /// No flags set
+    pub const NONE: Self = Self{ read: false, write: false, execute: false, alloc: false, init: false };
+
+    /// Check whether any flag is set in both
+    pub fn intersects(&self, other: &MemoryFlags) -> bool {
+        (self.read && other.read) || (self.write && other.write) || (self.execute && other.execute)
+            || (self.alloc && other.alloc) || (self.init && other.init)
+    }
+}
+
+impl fmt::Display for MemoryFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Flags are printed in linker order
+        for (set, c) in [(self.alloc, 'a'), (self.execute, 'x'), (self.read, 'r'), (self.write, 'w'), (self.init, 'l')] {
+            if set {
+                write!(f, "{}", c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MemoryAttrs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.flags)?;
+        if self.negated != MemoryFlags::NONE {
+            write!(f, " !{}", self.negated)?;
+        }
+        Ok(())
+    }
 }
 
 impl <'a> MemoryInfo<'a> {
 
-    fn header(s: &'a str) -> IResult<&'a str, (), ErrorTree<&'a str>> {
+    /// Check whether the provided address lies within this memory
+    pub fn contains(&self, addr: u64) -> bool {
+        addr >= self.origin && addr - self.origin < self.length
+    }
+
+    /// Check whether this memory is executable
+    pub fn is_executable(&self) -> bool {
+        self.attrs.map(|a| a.is_executable() ).unwrap_or(false)
+    }
+
+    /// Check whether sections with the provided flags are placed in this memory by default
+    pub fn accepts(&self, section: MemoryFlags) -> bool {
+        self.attrs.map(|a| a.accepts(section) ).unwrap_or(false)
+    }
+
+    fn header(s: &'a [u8]) -> IResult<&'a [u8], (), ErrorTree<&'a [u8]>> {
         let (o, _) = tuple((
             tag("Name"),
             space1,
@@ -61,7 +178,7 @@ impl <'a> MemoryInfo<'a> {
         Ok((o, ()))
     }
 
-    pub fn parse_block(s: &'a str) -> IResult<&'a str, Vec<Self>, ErrorTree<&'a str>> {
+    pub fn parse_block(s: &'a [u8]) -> IResult<&'a [u8], Vec<Self>, ErrorTree<&'a [u8]>> {
         let (o, (_, _, _, _, items, _)) = context(
             "memory config",
             tuple((
@@ -69,7 +186,7 @@ impl <'a> MemoryInfo<'a> {
                 many0(line_ending),
                 Self::header,
                 many0(line_ending),
-                many0(terminated(MemoryInfo::parse_item, line_ending)),
+                parse_items(terminated(MemoryInfo::parse_item, eol), is_block_end),
                 many0(line_ending),
             )) 
         )(s)?;
@@ -77,25 +194,19 @@ impl <'a> MemoryInfo<'a> {
         Ok((o, (items)))
     }
 
-    pub fn parse_item(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
+    pub fn parse_item(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
         let (o, r) = context(
             "memory",
             tuple((
-                take_while1(|c| c != ' '),
+                map(take_while1(|c| c != b' '), Name),
                 space1,
-                map_res(
-                    preceded(tag("0x"), take_while1(|c| is_hex_digit(c as u8) )),
-                    from_hex,
-                ),
+                parse_hex,  // Origin
                 space1,
-                map_res(
-                    preceded(tag("0x"), take_while1(|c| is_hex_digit(c as u8) )),
-                    from_hex,
-                ),
-                opt(tuple((
+                parse_hex,  // Length
+                opt(preceded(
                     space1,
-                    take_while(|c| c != '\r' && c != '\n'),
-                ))),
+                    map_opt(take_while1(|c| c != b'\r' && c != b'\n'), |v: &[u8]| MemoryAttrs::parse(v.trim_ascii_end()) ),
+                )),
 
                 //opt(line_ending),
                 //rest,
@@ -108,28 +219,61 @@ impl <'a> MemoryInfo<'a> {
                 name: r.0,
                 origin: r.2,
                 length: r.4,
-                attrs: r.5.map(|v| v.1),
+                attrs: r.5,
             },
         ))
     }
 }
 
 
+/// Width of the first column in archive member and as-needed library listings
+const ARCHIVE_COL: usize = 30;
+
+/// Width of the symbol column in common symbol listings
+const COMMON_COL: usize = 20;
+
+/// Split a reference (ie. `build/main.o (memcpy)`) into the referencing file and symbol,
+/// matching the symbol parentheses from the end as both may contain parentheses
+fn split_reference(s: &[u8]) -> Option<(Option<InputFile<'_>>, Name<'_>)> {
+    let s = s.trim_ascii_end().strip_suffix(b")")?;
+
+    let mut depth = 0usize;
+    let balanced = s.iter().rposition(|c| match c {
+        b')' => { depth += 1; false },
+        b'(' if depth == 0 => true,
+        b'(' => { depth -= 1; false },
+        _ => false,
+    });
+    let i = balanced.or_else(|| s.iter().position(|c| *c == b'(' ))?;
+
+    let file = Some(Name(s[..i].trim_ascii_end()))
+        .filter(|f| !f.is_empty() )
+        .map(InputFile::from_path);
+
+    Some((file, Name(&s[i+1..])))
+}
+
+/// Archive member included to satisfy a reference
 #[derive(Clone, PartialEq, Debug)]
 pub struct ArchiveInfo<'a> {
-    pub archive: &'a str,
-    pub object: &'a str,
-    pub symbol: &'a str,
+    pub archive: FilePath<'a>,
+    /// Included member object (ie. `compiler_builtins-....rcgu.o`)
+    pub member: Name<'a>,
+    /// Referencing file, where available
+    pub file: Option<InputFile<'a>>,
+    /// Referenced symbol causing the member to be included
+    pub symbol: Name<'a>,
 }
 
 impl <'a> ArchiveInfo<'a> {
-    pub fn parse_block(s: &'a str) -> IResult<&'a str, Vec<Self>, ErrorTree<&'a str>> {
-        let (o, (_, _, items, _)) = context(
+    pub fn parse_block(s: &'a [u8]) -> IResult<&'a [u8], Vec<Self>, ErrorTree<&'a [u8]>> {
+        let (o, (_, _, _, items, _)) = context(
             "references",
             tuple((
                 space0,
                 terminated(tag("Archive member included to satisfy reference by file (symbol)"), line_ending),
-                many0(terminated(ArchiveInfo::parse_item, line_ending)),
+                many0(line_ending),
+                parse_items(terminated(ArchiveInfo::parse_item, eol), is_block_end),
                 many0(line_ending),
             )) 
         )(s)?;
@@ -137,99 +281,390 @@ impl <'a> ArchiveInfo<'a> {
         Ok((o, (items)))
     }
 
-    pub fn parse_item(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
+    pub fn parse_item(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
         let (o, r) = context(
             "reference",
             tuple((
-                take_while1(|c| c != '('),
-                delimited(tag("("), is_not(")"), tag(")")),
-                line_ending,
-                space1,
-                take_while1(|c| c != ' '),
-                space0,
-                delimited(tag("("), is_not(")"), tag(")")),
+                // Included member (ie. `libc.a(memcpy.o)`), wrapped where this exceeds the column
+                map_opt(parse_column(ARCHIVE_COL), |v| match InputFile::from_path(v) {
+                    InputFile::Member{ archive, member } => Some((archive, member)),
+                    _ => None,
+                }),
+                map_opt(not_line_ending, split_reference),
             ))
         )(s)?;
 
         Ok((
             o,
             Self{
-                archive: r.0,
-                object: r.4,
-                symbol: r.6,
+                archive: r.0.0,
+                member: r.0.1,
+                file: r.1.0,
+                symbol: r.1.1,
             },
         ))
     }
+
+    /// Fetch the included archive member as an input file
+    pub fn input(&self) -> InputFile<'a> {
+        InputFile::Member{ archive: self.archive, member: self.member }
+    }
 }
 
 
+/// Shared library included by `--as-needed` to satisfy a reference
 #[derive(Clone, PartialEq, Debug)]
-pub struct SectionInfo<'a> {
-    pub group: &'a str,
-    pub addr: u64,
-    pub size: u64,
-    pub archive: &'a str,
+pub struct AsNeededInfo<'a> {
+    pub library: FilePath<'a>,
+    /// Referencing file, where available
+    pub file: Option<InputFile<'a>>,
+    pub symbol: Name<'a>,
 }
 
-impl <'a> SectionInfo<'a> {
-    pub fn parse_block(s: &'a str) -> IResult<&'a str, Vec<Self>, ErrorTree<&'a str>> {
+impl <'a> AsNeededInfo<'a> {
+    pub fn parse_block(s: &'a [u8]) -> IResult<&'a [u8], Vec<Self>, ErrorTree<&'a [u8]>> {
         let (o, (_, _, items, _)) = context(
-            "discarded sections",
+            "as-needed libraries",
             tuple((
-                tag("Discarded input sections"),
+                terminated(tag("As-needed library included to satisfy reference by file (symbol)"), line_ending),
                 many0(line_ending),
-                many0(terminated(SectionInfo::parse_item, line_ending)),
+                parse_items(terminated(AsNeededInfo::parse_item, eol), is_block_end),
                 many0(line_ending),
-            )) 
+            ))
         )(s)?;
 
         Ok((o, (items)))
     }
 
-    pub fn parse_item(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
+    pub fn parse_item(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
         let (o, r) = context(
-            "section",
+            "as-needed library",
             tuple((
-                space0,
-                take_while1(|c| c != ' '),
+                map(parse_column(ARCHIVE_COL), FilePath::from_name),
+                map_opt(not_line_ending, split_reference),
+            ))
+        )(s)?;
+
+        Ok((
+            o,
+            Self{
+                library: r.0,
+                file: r.1.0,
+                symbol: r.1.1,
+            },
+        ))
+    }
+}
+
+
+/// Common symbol allocated by the linker (from `-fcommon` objects)
+#[derive(Clone, PartialEq, Debug)]
+pub struct CommonSymbolInfo<'a> {
+    pub name: Name<'a>,
+    pub size: u64,
+    pub file: InputFile<'a>,
+}
+
+impl <'a> CommonSymbolInfo<'a> {
+    fn header(s: &'a [u8]) -> IResult<&'a [u8], (), ErrorTree<&'a [u8]>> {
+        let (o, _) = tuple((
+            tag("Common symbol"),
+            space1,
+            tag("size"),
+            space1,
+            tag("file"),
+            space0,
+        ))(s)?;
+
+        Ok((o, ()))
+    }
+
+    pub fn parse_block(s: &'a [u8]) -> IResult<&'a [u8], Vec<Self>, ErrorTree<&'a [u8]>> {
+        let (o, (_, _, _, _, items, _)) = context(
+            "common symbols",
+            tuple((
+                terminated(tag("Allocating common symbols"), line_ending),
+                many0(line_ending),
+                Self::header,
+                many0(line_ending),
+                parse_items(terminated(CommonSymbolInfo::parse_item, eol), is_block_end),
+                many0(line_ending),
+            ))
+        )(s)?;
+
+        Ok((o, (items)))
+    }
+
+    pub fn parse_item(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, r) = context(
+            "common symbol",
+            tuple((
+                parse_column(COMMON_COL),
+                parse_hex,
                 space1,
-                map_res(
-                    delimited(tag("0x"), is_not(" "), tag(" ")),
-                    from_hex,
+                InputFile::parse,
+            ))
+        )(s)?;
+
+        Ok((
+            o,
+            Self{
+                name: r.0,
+                size: r.1,
+                file: r.3,
+            },
+        ))
+    }
+}
+
+
+/// Discarded input section (ie. `.text.unused  0x0  0x10 build/main.o`)
+#[derive(Clone, PartialEq, Debug)]
+pub struct SectionInfo<'a> {
+    /// Input section name
+    pub name: Name<'a>,
+    pub addr: u64,
+    pub size: u64,
+    /// Input file containing the discarded section, where available
+    pub file: Option<InputFile<'a>>,
+}
+
+/// Input section kind, inferred from the section name
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputKind {
+    /// Code (ie. `.text*`, `.init`)
+    Text,
+    /// Read-only data (`.rodata*`)
+    ReadOnly,
+    /// Initialised data (`.data*`)
+    Data,
+    /// Zero-initialised data (`.bss*`, `COMMON`)
+    Bss,
+    /// Section group (`.group`)
+    Group,
+    /// Debug information (ie. `.debug*`, `.stab*`)
+    Debug,
+    Other,
+}
+
+impl InputKind {
+    /// Classify an input section by name
+    pub fn from_name(name: Name) -> Self {
+        if is_debug_section(name) {
+            InputKind::Debug
+        } else if name.matches_section(CODE_SECTIONS) {
+            InputKind::Text
+        } else if name.matches_section(&[".rodata", ".srodata"]) {
+            InputKind::ReadOnly
+        } else if name.matches_section(&[".data", ".sdata", ".tdata"]) {
+            InputKind::Data
+        } else if name.matches_section(ZERO_INIT_SECTIONS) {
+            InputKind::Bss
+        } else if name == ".group" {
+            InputKind::Group
+        } else {
+            InputKind::Other
+        }
+    }
+}
+
+impl <'a> SectionInfo<'a> {
+    pub fn parse_block(s: &'a [u8]) -> IResult<&'a [u8], Vec<Self>, ErrorTree<&'a [u8]>> {
+        context(
+            "discarded sections",
+            alt((
+                map(
+                    tuple((
+                        tag("Discarded input sections"),
+                        many0(line_ending),
+                        parse_items(SectionInfo::parse_item, is_block_end),
+                        many0(line_ending),
+                    )),
+                    |(_, _, items, _)| items,
                 ),
-                space1,
-                map_res(
-                    delimited(tag("0x"), is_not(" "), tag(" ")),
-                    from_hex,
+                // Maps with nothing discarded include a placeholder instead
+                map(
+                    tuple((
+                        tag("There are no discarded input sections"),
+                        many0(line_ending),
+                    )),
+                    |_| vec![],
                 ),
-                take_while1(|c| c != '\r' && c != '\n'),
+            ))
+        )(s)
+    }
+
+    pub fn parse_item(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, r) = context(
+            "discarded section",
+            tuple((
+                space1,
+                parse_path,         // Section name, wrapped where this exceeds the column
+                parse_column_sep,
+                parse_hex,          // Address
+                space1,
+                parse_hex,          // Size
+                opt(preceded(
+                    space1,
+                    InputFile::parse,
+                )),
+                eol,
+                // Size before relaxing, where applicable
+                opt(parse_pre_relax_size),
             ))
         )(s)?;
 
         Ok((
             o,
             Self{
-                group: r.1,
+                name: r.1,
                 addr: r.3,
                 size: r.5,
-                archive: r.6,
+                file: r.6,
             },
         ))
     }
+
+    /// Classify this section by name
+    pub fn kind(&self) -> InputKind {
+        InputKind::from_name(self.name)
+    }
+}
+
+
+
+/// Cross reference table entry (from `--cref`), listing the files referring to a symbol
+#[derive(Clone, PartialEq, Debug)]
+pub struct CrossReference<'a> {
+    pub symbol: Name<'a>,
+    /// Files defining then referencing the symbol, in map order (see [`MapFile::definitions`](crate::MapFile::definitions))
+    pub files: Vec<InputFile<'a>>,
+}
+
+/// Column at which cross reference files are printed
+const CREF_FILE_COL: usize = 50;
+
+impl <'a> CrossReference<'a> {
+    pub fn parse_block(s: &'a [u8]) -> IResult<&'a [u8], Vec<Self>, ErrorTree<&'a [u8]>> {
+        let (o, (_, _, _, _, items, _)) = context(
+            "cross references",
+            tuple((
+                terminated(tag("Cross Reference Table"), line_ending),
+                many0(line_ending),
+                tuple((tag("Symbol"), space1, tag("File"), space0)),
+                many0(line_ending),
+                parse_items(CrossReference::parse_item, is_block_end),
+                many0(line_ending),
+            ))
+        )(s)?;
+
+        Ok((o, (items)))
+    }
+
+    pub fn parse_item(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (first, _, rest)) = context(
+            "cross reference",
+            tuple((
+                // Symbol and first file, symbols may include spaces (ie. demangled C++)
+                recognize(tuple((
+                    take_while1(|c: u8| !c.is_ascii_whitespace() ),
+                    not_line_ending,
+                ))),
+                alt((line_ending, eof)),
+                // Further files are indented to the file column, and may contain spaces
+                many0(delimited(
+                    space1,
+                    InputFile::parse,
+                    eol,
+                )),
+            ))
+        )(s)?;
+
+        // Short symbols are padded to the file column, longer ones followed by a single space
+        // (as may be a space within the symbol at the column), or wrapped with the file on
+        // the following line where no file follows
+        let (symbol, file) = match first.get(..CREF_FILE_COL) {
+            Some(head) if head.ends_with(b"  ") && first.len() > CREF_FILE_COL => (head, &first[CREF_FILE_COL..]),
+            _ => split_symbol(first),
+        };
+
+        let file = Name(file.trim_ascii());
+        let files = (!file.is_empty()).then(|| InputFile::from_path(file) )
+            .into_iter()
+            .chain(rest)
+            .collect();
+
+        Ok((o, Self{ symbol: Name(symbol.trim_ascii_end()), files }))
+    }
 }
 
+/// Split a symbol from the following file at the first space outside of brackets,
+/// as demangled symbols may contain spaces (ie. `foo(int, char)`) as may file paths
+fn split_symbol(s: &[u8]) -> (&[u8], &[u8]) {
+    let mut depth = 0usize;
+    for (i, c) in s.iter().enumerate() {
+        match c {
+            b'(' | b'<' | b'[' => depth += 1,
+            b')' | b'>' | b']' => depth = depth.saturating_sub(1),
+            b' ' if depth == 0 => return (&s[..i], &s[i+1..]),
+            _ => (),
+        }
+    }
+    (s, &s[s.len()..])
+}
 
+/// Unrecognised map block, preserved as raw text
+#[derive(Clone, PartialEq, Debug)]
+pub struct RawBlock<'a> {
+    pub heading: Name<'a>,
+    pub body: Name<'a>,
+}
+
+impl <'a> RawBlock<'a> {
+    /// Parse an unrecognised block, from an unindented heading line followed by either an indented body,
+    /// or a blank line then a body continuing to the next blank line or known heading
+    /// (ie. `Merging program properties`)
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (heading, _, body)) = context(
+            "block",
+            tuple((
+                recognize(tuple((
+                    not(parse_heading),
+                    take_while1(|c: u8| !c.is_ascii_whitespace() ),
+                    not_line_ending,
+                ))),
+                line_ending,
+                alt((
+                    recognize(many1(tuple((
+                        space1,
+                        not_line_ending,
+                        alt((line_ending, eof)),
+                    )))),
+                    preceded(line_ending, recognize(many0(tuple((
+                        not(alt((parse_heading, line_ending, eof))),
+                        not_line_ending,
+                        alt((line_ending, eof)),
+                    ))))),
+                )),
+            ))
+        )(s)?;
+
+        Ok((o, Self{ heading: Name(heading), body: Name(body.trim_ascii_end()) }))
+    }
+}
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::objects::SectionClass;
 
     use pretty_assertions::assert_eq;
 
     const FILES: &[(FileInfo, &str)] = &[
         (
             FileInfo{
-                name: "stm32/pendsv.o",
+                name: InputFile::Path(FilePath::new("stm32/pendsv.o")),
+                group: None,
             },
             "LOAD stm32/pendsv.o",
         ),
@@ -238,7 +673,7 @@ mod test {
     #[test]
     fn parse_file_info() {
         for (v, raw) in FILES {
-            let (_, p) = FileInfo::parse(raw).unwrap();
+            let (_, p) = FileInfo::parse(raw.as_bytes()).unwrap();
             assert_eq!(&p, v);
         }
     }
@@ -246,68 +681,362 @@ mod test {
     const MEMORIES: &[(MemoryInfo, &str)] = &[
         (
             MemoryInfo{
-                name: "FLASH",
+                name: Name::new("FLASH"),
                 origin: 0x0000000008040000,
                 length: 0x00000000000c0000,
-                attrs: Some("xr"),
+                attrs: Some(MemoryAttrs{
+                    flags: MemoryFlags{ read: true, execute: true, ..MemoryFlags::NONE },
+                    negated: MemoryFlags::NONE,
+                }),
             },
             "FLASH            0x0000000008040000 0x00000000000c0000 xr",
         ), (
             MemoryInfo{
-                name: "*default*",
+                name: Name::new("*default*"),
                 origin: 0x0000000000000000,
                 length: 0xffffffffffffffff,
                 attrs: None,
             },
             "*default*        0x0000000000000000 0xffffffffffffffff"
-        )
+        ), (
+            MemoryInfo{
+                name: Name::new("RAM"),
+                origin: 0x20000000,
+                length: 0x00020000,
+                attrs: Some(MemoryAttrs{
+                    flags: MemoryFlags{ read: true, write: true, execute: true, ..MemoryFlags::NONE },
+                    negated: MemoryFlags::NONE,
+                }),
+            },
+            "RAM              0x20000000         0x00020000         xrw",
+        ),
     ];
 
     #[test]
     fn parse_memory_info() {
         for (v, raw) in MEMORIES {
-            let (_, p) = MemoryInfo::parse_item(raw).unwrap();
+            let (_, p) = MemoryInfo::parse_item(raw.as_bytes()).unwrap();
             assert_eq!(&p, v);
         }
     }
 
+    #[test]
+    fn parse_memory_attrs() {
+        let code = MemoryFlags{ read: true, execute: true, ..MemoryFlags::NONE };
+        let data = MemoryFlags{ write: true, alloc: true, init: true, ..MemoryFlags::NONE };
+
+        let attrs = &[
+            ("xr", "xr", true, false),
+            ("awl", "awl", false, true),
+            ("rx!w", "xr !w", true, false),
+            ("rx !w", "xr !w", true, false),
+            ("!x", " !x", false, false),
+        ];
+
+        for (raw, display, accepts_code, accepts_data) in attrs {
+            let a = MemoryAttrs::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&a.to_string(), display);
+            assert_eq!(a.accepts(code), *accepts_code, "{} code", raw);
+            assert_eq!(a.accepts(data), *accepts_data, "{} data", raw);
+        }
+
+        let a = MemoryAttrs::parse(b"rx!w").unwrap();
+        assert!(a.is_executable());
+        assert!(!a.is_writable());
+        assert!(a.negated.write);
+
+        assert_eq!(MemoryAttrs::parse(b"rq"), None);
+    }
+
     const ARCHIVES: &[(ArchiveInfo, &str)] = &[
         (
             ArchiveInfo{
-                archive: "build/something.a",
-                object: "build/something.o",
-                symbol: "some_symbol_name",
+                archive: FilePath::new("build/something.a"),
+                member: Name::new("something.0.rcgu.o"),
+                file: Some(InputFile::Path(FilePath::new("build/something.o"))),
+                symbol: Name::new("some_symbol_name"),
             },
             r#"build/something.a(something.0.rcgu.o)
             build/something.o (some_symbol_name)"#
-        )
+        ), (
+            ArchiveInfo{
+                archive: FilePath::new("libc.a"),
+                member: Name::new("memcpy.o"),
+                file: Some(InputFile::Member{ archive: FilePath::new("libc.a"), member: Name::new("printf.o") }),
+                symbol: Name::new("memcpy"),
+            },
+            "libc.a(memcpy.o)              libc.a(printf.o) (memcpy)",
+        ), (
+            ArchiveInfo{
+                archive: FilePath::new("libstdc++.a"),
+                member: Name::new("ios_init.o"),
+                file: None,
+                symbol: Name::new("std::ios_base::Init::Init()"),
+            },
+            "libstdc++.a(ios_init.o)       (std::ios_base::Init::Init())",
+        ), (
+            ArchiveInfo{
+                archive: FilePath::new("my libs/libhal.a"),
+                member: Name::new("gpio.o"),
+                file: Some(InputFile::Path(FilePath::new(r"C:\Users\Jo Bloggs\build\main.o"))),
+                symbol: Name::new("gpio_init"),
+            },
+            r"my libs/libhal.a(gpio.o)      C:\Users\Jo Bloggs\build\main.o (gpio_init)",
+        ), (
+            ArchiveInfo{
+                archive: FilePath::new(r"C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a"),
+                member: Name::new("lib_a-memcpy.o"),
+                file: Some(InputFile::Member{
+                    archive: FilePath::new(r"C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a"),
+                    member: Name::new("lib_a-printf.o"),
+                }),
+                symbol: Name::new("memcpy"),
+            },
+            "C:\\Program Files (x86)\\GNU Arm Embedded\\lib\\libc.a(lib_a-memcpy.o)\n                              C:\\Program Files (x86)\\GNU Arm Embedded\\lib\\libc.a(lib_a-printf.o) (memcpy)",
+        ),
     ];
 
     #[test]
     fn parse_archive_info() {
         for (v, raw) in ARCHIVES {
-            let (_, p) = ArchiveInfo::parse_item(raw).unwrap();
+            let (_, p) = ArchiveInfo::parse_item(raw.as_bytes()).unwrap();
             assert_eq!(&p, v);
         }
     }
 
+    const AS_NEEDED: &[(AsNeededInfo, &str)] = &[
+        (
+            AsNeededInfo{
+                library: FilePath::new("libm.so.6"),
+                file: Some(InputFile::Path(FilePath::new("/tmp/ccq3Xb1d.o"))),
+                symbol: Name::new("sin@@GLIBC_2.2.5"),
+            },
+            "libm.so.6                     /tmp/ccq3Xb1d.o (sin@@GLIBC_2.2.5)",
+        ), (
+            AsNeededInfo{
+                library: FilePath::new("/usr/lib/x86_64-linux-gnu/libstdc++.so.6"),
+                file: Some(InputFile::Path(FilePath::new("build/main.o"))),
+                symbol: Name::new("std::ios_base::Init::Init()@@GLIBCXX_3.4"),
+            },
+            "/usr/lib/x86_64-linux-gnu/libstdc++.so.6\n                              build/main.o (std::ios_base::Init::Init()@@GLIBCXX_3.4)",
+        ), (
+            AsNeededInfo{
+                library: FilePath::new("libc.so.6"),
+                file: None,
+                symbol: Name::new("__libc_start_main@@GLIBC_2.34"),
+            },
+            "libc.so.6                     (__libc_start_main@@GLIBC_2.34)",
+        ),
+    ];
+
+    #[test]
+    fn parse_as_needed() {
+        for (v, raw) in AS_NEEDED {
+            let (_, p) = AsNeededInfo::parse_item(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+        }
+    }
+
+    #[test]
+    fn parse_cross_references() {
+        let raw = "Cross Reference Table
+
+Symbol                                            File
+HAL_GetTick                                       build/firmware/embed/trezorhal/common.o
+                                                  build/firmware/embed/trezorhal/sdcard.o
+                                                  build/firmware/embed/trezorhal/usb.o
+_ZN4core3fmt9Formatter3pad17h5e4b1f8e0c9a2d31E_long build/libtrezor_lib.a(core-1.o)
+                                                  build/libtrezor_lib.a(trezor_lib-2.o)
+std::ios_base::Init::Init()                       /usr/lib/libstdc++.so.6
+";
+
+        let v = vec![
+            CrossReference{
+                symbol: Name::new("HAL_GetTick"),
+                files: vec![
+                    InputFile::Path(FilePath::new("build/firmware/embed/trezorhal/common.o")),
+                    InputFile::Path(FilePath::new("build/firmware/embed/trezorhal/sdcard.o")),
+                    InputFile::Path(FilePath::new("build/firmware/embed/trezorhal/usb.o")),
+                ],
+            },
+            CrossReference{
+                symbol: Name::new("_ZN4core3fmt9Formatter3pad17h5e4b1f8e0c9a2d31E_long"),
+                files: vec![
+                    InputFile::Member{ archive: FilePath::new("build/libtrezor_lib.a"), member: Name::new("core-1.o") },
+                    InputFile::Member{ archive: FilePath::new("build/libtrezor_lib.a"), member: Name::new("trezor_lib-2.o") },
+                ],
+            },
+            CrossReference{
+                symbol: Name::new("std::ios_base::Init::Init()"),
+                files: vec![InputFile::Path(FilePath::new("/usr/lib/libstdc++.so.6"))],
+            },
+        ];
+
+        let (o, p) = CrossReference::parse_block(raw.as_bytes()).unwrap();
+        assert_eq!(p, v);
+        assert!(o.is_empty());
+
+
+        // Spaces within long symbols at the file column are not padding
+        let raw = "mylib::drivers::UartControllerImpl::set_mode(int, bool) build/uart.o
+                                                  build/main.o
+";
+        let (_, p) = CrossReference::parse_item(raw.as_bytes()).unwrap();
+        assert_eq!(p, CrossReference{
+            symbol: Name::new("mylib::drivers::UartControllerImpl::set_mode(int, bool)"),
+            files: vec![
+                InputFile::Path(FilePath::new("build/uart.o")),
+                InputFile::Path(FilePath::new("build/main.o")),
+            ],
+        });
+
+        // Symbols without a file on the same line are wrapped
+        let raw = "mylib::drivers::UartControllerImpl::set_mode(int, bool)
+                                                  build/uart.o
+";
+        let (_, p) = CrossReference::parse_item(raw.as_bytes()).unwrap();
+        assert_eq!(p.symbol, Name::new("mylib::drivers::UartControllerImpl::set_mode(int, bool)"));
+        assert_eq!(p.files, &[InputFile::Path(FilePath::new("build/uart.o"))]);
+
+        // Files may contain spaces, including after long symbols
+        let raw = r"_ZN4core3fmt9Formatter3pad17h5e4b1f8e0c9a2d31E_long C:\My Project\main.o
+                                                  C:\My Project\lib a.a(b.o)
+";
+        let (_, p) = CrossReference::parse_item(raw.as_bytes()).unwrap();
+        assert_eq!(p, CrossReference{
+            symbol: Name::new("_ZN4core3fmt9Formatter3pad17h5e4b1f8e0c9a2d31E_long"),
+            files: vec![
+                InputFile::Path(FilePath::new(r"C:\My Project\main.o")),
+                InputFile::Member{ archive: FilePath::new(r"C:\My Project\lib a.a"), member: Name::new("b.o") },
+            ],
+        });
+    }
+
+    const COMMONS: &[(CommonSymbolInfo, &str)] = &[
+        (
+            CommonSymbolInfo{
+                name: Name::new("mp_state_ctx"),
+                size: 0x2c8,
+                file: InputFile::Path(FilePath::new("build/firmware/vendor/micropython/py/mpstate.o")),
+            },
+            "mp_state_ctx        0x2c8             build/firmware/vendor/micropython/py/mpstate.o",
+        ), (
+            CommonSymbolInfo{
+                name: Name::new("usb_dev_handle_allocated_buffer"),
+                size: 0x4,
+                file: InputFile::Path(FilePath::new("build/firmware/embed/trezorhal/usb.o")),
+            },
+            "usb_dev_handle_allocated_buffer\n                    0x4               build/firmware/embed/trezorhal/usb.o",
+        ),
+    ];
+
+    #[test]
+    fn parse_common_symbols() {
+        for (v, raw) in COMMONS {
+            let (_, p) = CommonSymbolInfo::parse_item(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+        }
+
+        let raw = "Allocating common symbols
+Common symbol       size              file
+
+mp_state_ctx        0x2c8             build/firmware/vendor/micropython/py/mpstate.o
+usb_dev_handle_allocated_buffer
+                    0x4               build/firmware/embed/trezorhal/usb.o
+
+Discarded input sections
+";
+
+        let (o, p) = CommonSymbolInfo::parse_block(raw.as_bytes()).unwrap();
+        assert_eq!(p, COMMONS.iter().map(|v| v.0.clone() ).collect::<Vec<_>>());
+        assert!(o.starts_with(b"Discarded input sections"));
+
+        // Files may contain spaces
+        let (_, p) = CommonSymbolInfo::parse_item(b"buffer              0x40              build dir/main.o\n").unwrap();
+        assert_eq!(p.file, InputFile::Path(FilePath::new("build dir/main.o")));
+    }
+
     const SECTIONS: &[(SectionInfo, &str)] = &[
         (
             SectionInfo{
-                group: ".group",
+                name: Name::new(".group"),
                 addr: 0x01,
                 size: 0x0c,
-                archive: "build/something.o",
+                file: Some(InputFile::Path(FilePath::new("build/something.o"))),
             },
             " .group         0x0000000000000001        0xc build/something.o\r\n",
+        ), (
+            SectionInfo{
+                name: Name::new(".text._ZN4core3fmt9Formatter3pad17h5e4b1f8e0c9a2d31E"),
+                addr: 0x00,
+                size: 0x1d4,
+                file: Some(InputFile::Path(FilePath::new("build/something.o"))),
+            },
+            " .text._ZN4core3fmt9Formatter3pad17h5e4b1f8e0c9a2d31E\n                0x0000000000000000      0x1d4 build/something.o\n",
+        ), (
+            SectionInfo{
+                name: Name::new(".rodata.cst16"),
+                addr: 0x00,
+                size: 0x20,
+                file: Some(InputFile::Member{ archive: FilePath::new("/usr/lib/arm-none-eabi/lib/libm.a"), member: Name::new("lib_a-s_sin.o") }),
+            },
+            " .rodata.cst16  0x0000000000000000       0x20 /usr/lib/arm-none-eabi/lib/libm.a(lib_a-s_sin.o)\n                                         0x28 (size before relaxing)\n",
+        ), (
+            SectionInfo{
+                name: Name::new(".ARM.exidx.text._ZN4core9panicking5panic17h0d4b1e8b4f3a2c11E"),
+                addr: 0x00,
+                size: 0x08,
+                file: None,
+            },
+            " .ARM.exidx.text._ZN4core9panicking5panic17h0d4b1e8b4f3a2c11E\n                0x0000000000000000        0x8\n",
         ),
     ];
 
     #[test]
     fn parse_sections() {
         for (v, raw) in SECTIONS {
-            let (_, p) = SectionInfo::parse_item(raw).unwrap();
+            let (o, p) = SectionInfo::parse_item(raw.as_bytes()).unwrap();
             assert_eq!(&p, v);
+            assert!(o.is_empty());
+        }
+
+        let raw = SECTIONS.iter().map(|v| v.1 ).collect::<Vec<_>>().join("");
+        let raw = format!("Discarded input sections\n\n{}\nMemory Configuration", raw);
+        let (o, p) = SectionInfo::parse_block(raw.as_bytes()).unwrap();
+        assert!(o.starts_with(b"Memory Configuration"));
+        assert_eq!(p.iter().map(|s| s.kind() ).collect::<Vec<_>>(), &[
+            InputKind::Group,
+            InputKind::Text,
+            InputKind::ReadOnly,
+            InputKind::Other,
+        ]);
+    }
+
+    #[test]
+    fn classify_input_sections() {
+        let names = &[
+            (".text", InputKind::Text),
+            (".text.main", InputKind::Text),
+            (".rodata.str1.1", InputKind::ReadOnly),
+            (".data.rel.ro", InputKind::Data),
+            (".bss.buffer", InputKind::Bss),
+            ("COMMON", InputKind::Bss),
+            (".group", InputKind::Group),
+            (".debug_info", InputKind::Debug),
+            (".textual", InputKind::Other),
+            // Shared with output section classification
+            (".init", InputKind::Text),
+            (".stab", InputKind::Debug),
+            (".gnu.debuglto_.debug_info", InputKind::Debug),
+        ];
+
+        for (name, kind) in names {
+            assert_eq!(&InputKind::from_name(Name::new(name)), kind, "{}", name);
+            assert_eq!(
+                SectionClass::from_name(Name::new(name)) == Some(SectionClass::Debug),
+                *kind == InputKind::Debug,
+                "{}", name,
+            );
         }
     }
 }
@@ -2,8 +2,32 @@
 mod info;
 pub use info::*;
 
+mod file;
+pub use file::*;
+
 mod section;
 pub use section::*;
 
 mod symbol;
 pub use symbol::*;
+
+mod spec;
+pub use spec::*;
+
+mod command;
+pub use command::*;
+
+mod assignment;
+pub use assignment::*;
+
+mod expr;
+pub use expr::*;
+
+mod statement;
+pub use statement::*;
+
+mod name;
+pub use name::*;
+
+mod path;
+pub use path::*;
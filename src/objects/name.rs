@@ -0,0 +1,150 @@
+use std::{borrow::{Borrow, Cow}, ffi::OsStr, fmt};
+
+/// Name or path from the map (ie. `.text`, `build/main.o`), as raw bytes
+/// as maps may contain non-UTF-8 paths or symbols
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Name<'a>(pub &'a [u8]);
+
+impl <'a> Name<'a> {
+    /// Create a name from a string
+    pub const fn new(s: &'a str) -> Self {
+        Self(s.as_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Fetch the name as a string, where this is valid UTF-8
+    pub fn to_str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.0).ok()
+    }
+
+    /// Fetch the name as a string, replacing invalid UTF-8 sequences
+    pub fn to_string_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.0)
+    }
+
+    /// Fetch the name as an OS string (ie. for file paths)
+    #[cfg(unix)]
+    pub fn to_os_str(&self) -> Cow<'a, OsStr> {
+        use std::os::unix::ffi::OsStrExt;
+        Cow::Borrowed(OsStr::from_bytes(self.0))
+    }
+
+    /// Fetch the name as an OS string (ie. for file paths)
+    #[cfg(not(unix))]
+    pub fn to_os_str(&self) -> Cow<'a, OsStr> {
+        match self.to_string_lossy() {
+            Cow::Borrowed(s) => Cow::Borrowed(OsStr::new(s)),
+            Cow::Owned(s) => Cow::Owned(s.into()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.0.starts_with(prefix.as_bytes())
+    }
+
+    /// Strip a prefix from the name
+    pub fn strip_prefix(&self, prefix: &str) -> Option<Name<'a>> {
+        self.0.strip_prefix(prefix.as_bytes()).map(Name)
+    }
+
+    /// Check whether the name matches any of the provided section names, either whole
+    /// or with a dotted suffix (ie. `.text` matches `.text.main` but not `.textual`)
+    pub fn matches_section(&self, names: &[&str]) -> bool {
+        names.iter().any(|n| match self.strip_prefix(n) {
+            Some(r) => r.is_empty() || r.starts_with("."),
+            None => false,
+        })
+    }
+
+    /// Split the name at the first instance of the provided separator
+    pub fn split_once(&self, sep: u8) -> Option<(Name<'a>, Name<'a>)> {
+        let i = self.0.iter().position(|c| *c == sep )?;
+        Some((Name(&self.0[..i]), Name(&self.0[i+1..])))
+    }
+
+    /// Split the name at the last instance of the provided separator
+    pub fn rsplit_once(&self, sep: u8) -> Option<(Name<'a>, Name<'a>)> {
+        let i = self.0.iter().rposition(|c| *c == sep )?;
+        Some((Name(&self.0[..i]), Name(&self.0[i+1..])))
+    }
+}
+
+impl <'a> From<&'a str> for Name<'a> {
+    fn from(s: &'a str) -> Self {
+        Self(s.as_bytes())
+    }
+}
+
+impl <'a> From<&'a [u8]> for Name<'a> {
+    fn from(s: &'a [u8]) -> Self {
+        Self(s)
+    }
+}
+
+impl <'a> Borrow<[u8]> for Name<'a> {
+    fn borrow(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl <'a> PartialEq<str> for Name<'a> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl <'a, 'b> PartialEq<&'b str> for Name<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl <'a> fmt::Display for Name<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+impl <'a> fmt::Debug for Name<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn names() {
+        let n = Name::new("build/main.o");
+        assert_eq!(n, "build/main.o");
+        assert_eq!(n.to_str(), Some("build/main.o"));
+        assert_eq!(n.to_os_str(), OsStr::new("build/main.o"));
+
+        // Latin-1 paths are preserved as bytes
+        let n = Name(b"build/caf\xe9.o");
+        assert_eq!(n.to_str(), None);
+        assert_eq!(n.to_string_lossy(), "build/caf\u{fffd}.o");
+        assert_eq!(n.as_bytes(), b"build/caf\xe9.o");
+
+        // Section names match whole or with dotted suffixes
+        assert!(Name::new(".text").matches_section(&[".text"]));
+        assert!(Name::new(".text.main").matches_section(&[".data", ".text"]));
+        assert!(!Name::new(".textual").matches_section(&[".text"]));
+        assert!(!Name::new(".init_array").matches_section(&[".init"]));
+    }
+}
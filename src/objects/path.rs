@@ -0,0 +1,161 @@
+use std::{fmt, path::PathBuf};
+
+use super::Name;
+
+/// File path from the map (ie. `build/main.o`, `C:\build\main.o`), preserved as written
+/// with `/` and `\` both treated as separators, independent of the host platform
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct FilePath<'a>(pub Name<'a>);
+
+impl <'a> FilePath<'a> {
+    /// Create a path from a string
+    pub const fn new(s: &'a str) -> Self {
+        Self(Name::new(s))
+    }
+
+    /// Create a path from map text, removing enclosing quotes (ie. `"My Project/main.o"`)
+    pub fn from_name(n: Name<'a>) -> Self {
+        let v = n.as_bytes().strip_prefix(b"\"")
+            .and_then(|v| v.strip_suffix(b"\"") )
+            .unwrap_or(n.as_bytes());
+        Self(Name(v))
+    }
+
+    pub fn as_name(&self) -> Name<'a> {
+        self.0
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0.as_bytes()
+    }
+
+    /// Fetch the Windows drive prefix, where present (ie. `C:`)
+    pub fn prefix(&self) -> Option<Name<'a>> {
+        match self.as_bytes() {
+            v @ [d, b':', ..] if d.is_ascii_alphabetic() => Some(Name(&v[..2])),
+            _ => None,
+        }
+    }
+
+    /// Check whether this path is absolute (ie. `/usr/lib/libc.a`, `C:\lib\libc.a`)
+    pub fn is_absolute(&self) -> bool {
+        matches!(self.without_prefix().first(), Some(b'/' | b'\\'))
+    }
+
+    /// Check whether this path was written by a Windows host (ie. `C:\build\main.o`)
+    pub fn is_windows(&self) -> bool {
+        self.prefix().is_some() || self.as_bytes().contains(&b'\\')
+    }
+
+    /// Iterate over path components, splitting on both `/` and `\` and skipping empty and `.` components
+    pub fn components(&self) -> impl Iterator<Item=Name<'a>> + 'a {
+        self.without_prefix()
+            .split(|c| *c == b'/' || *c == b'\\' )
+            .filter(|c| !c.is_empty() && *c != b".")
+            .map(Name)
+    }
+
+    /// Fetch the final path component (ie. `main.o`)
+    pub fn file_name(&self) -> Option<Name<'a>> {
+        self.components().last()
+    }
+
+    /// Fetch the extension of the final path component (ie. `o` for `main.o`)
+    pub fn extension(&self) -> Option<Name<'a>> {
+        let f = self.file_name()?.as_bytes();
+        let i = f.iter().rposition(|c| *c == b'.' ).filter(|i| *i > 0 )?;
+        Some(Name(&f[i+1..]))
+    }
+
+    /// Format this path with `/` separators (ie. `C:/build/main.o`)
+    pub fn normalised(&self) -> String {
+        String::from_utf8_lossy(&self.join(b'/')).into_owned()
+    }
+
+    /// Convert this path to a native path, using the separator for the host platform
+    pub fn to_path_buf(&self) -> PathBuf {
+        let v = self.join(std::path::MAIN_SEPARATOR as u8);
+        PathBuf::from(Name(&v).to_os_str().into_owned())
+    }
+
+    /// Fetch the path following any drive prefix
+    fn without_prefix(&self) -> &'a [u8] {
+        let v = self.as_bytes();
+        &v[self.prefix().map(|p| p.len() ).unwrap_or(0)..]
+    }
+
+    /// Rebuild the path from its prefix and components with the provided separator
+    fn join(&self, sep: u8) -> Vec<u8> {
+        let mut v = self.prefix().map(|p| p.as_bytes().to_vec() ).unwrap_or_default();
+        if self.is_absolute() {
+            v.push(sep);
+        }
+        for (i, c) in self.components().enumerate() {
+            if i > 0 {
+                v.push(sep);
+            }
+            v.extend_from_slice(c.as_bytes());
+        }
+        v
+    }
+}
+
+impl <'a> From<&'a str> for FilePath<'a> {
+    fn from(s: &'a str) -> Self {
+        Self(Name::from(s))
+    }
+}
+
+impl <'a> PartialEq<str> for FilePath<'a> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl <'a, 'b> PartialEq<&'b str> for FilePath<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl <'a> fmt::Display for FilePath<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl <'a> fmt::Debug for FilePath<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn file_paths() {
+        let p = FilePath::new("build/firmware/main.o");
+        assert!(!p.is_absolute() && !p.is_windows());
+        assert_eq!(p.file_name(), Some(Name::new("main.o")));
+        assert_eq!(p.extension(), Some(Name::new("o")));
+
+        let p = FilePath::new(r"C:\Users\Jo Bloggs\My Project\build\main.o");
+        assert!(p.is_absolute() && p.is_windows());
+        assert_eq!(p.prefix(), Some(Name::new("C:")));
+        assert_eq!(p.file_name(), Some(Name::new("main.o")));
+        assert_eq!(p.normalised(), "C:/Users/Jo Bloggs/My Project/build/main.o");
+
+        let p = FilePath::new("/opt/vendor sdk/./lib/libhal.a");
+        assert!(p.is_absolute() && !p.is_windows());
+        assert_eq!(p.components().count(), 4);
+        assert_eq!(p.normalised(), "/opt/vendor sdk/lib/libhal.a");
+        assert_eq!(p.to_path_buf(), std::path::Path::new("/opt/vendor sdk").join("lib").join("libhal.a"));
+
+        let p = FilePath::from_name(Name::new("\"build dir/main.o\""));
+        assert_eq!(p, "build dir/main.o");
+    }
+}
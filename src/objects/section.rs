@@ -1,96 +1,456 @@
 use nom::{
     IResult,
-    error::context, sequence::{tuple, delimited, terminated, preceded}, 
-    bytes::complete::{take_while1, tag, is_not, take_until1}, 
-    character::complete::{line_ending, space1, space0},
-    combinator::{map_res, opt}, multi::many0,
+    error::{context, ErrorKind, ParseError, ContextError}, sequence::{tuple, preceded, delimited},
+    bytes::complete::{tag, take_while1},
+    character::{complete::{line_ending, space0, space1}, is_hex_digit},
+    combinator::{opt, map, map_res, value, peek, eof, recognize}, multi::many0, branch::alt,
 };
 
 use nom_supreme::error::ErrorTree;
 
-use crate::{parse_hex, parse_path};
-use super::{Symbol, SymbolKind};
+use log::trace;
 
-/// Memories included in output binary
+use crate::{from_hex, parse_hex, parse_path, parse_column_sep, parse_pre_relax_size, parse_items, is_block_end, eol};
+use super::{Symbol, InputSpec, InputFile, Assignment, Data, Assert, Name};
+
+/// Output section in application binary (ie. `.flash`), containing input section objects
 #[derive(Clone, PartialEq, Debug)]
 pub struct Section<'a> {
-    pub name: Option<&'a str>,
+    pub name: Option<Name<'a>>,
+    /// Virtual (runtime) address
     pub addr: Option<u64>,
     pub size: Option<u64>,
+    /// Load address, where this differs from `addr`
+    pub load_addr: Option<u64>,
+    /// Section type, where specified (ie. `(NOLOAD)`), ld does not print this so
+    /// zero-initialised sections are identified by [`Section::class`] instead
+    pub kind: Option<SectionKind>,
+
+    /// Input section rules (ie. `*(.text*)`), referenced by [`Object::rule`]
+    pub rules: Vec<InputSpec<'a>>,
+    /// Section contents, in map order
+    pub entries: Vec<Entry<'a>>,
+}
+
+/// Output section type (ie. `.noinit (NOLOAD)`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SectionKind {
+    /// Allocated without contents (`NOLOAD`)
+    NoLoad,
+    /// Read-only (`READONLY`)
+    ReadOnly,
+    /// Not allocated, dummy section (`DSECT`)
+    DSect,
+    /// Not allocated, copied from another section (`COPY`)
+    Copy,
+    /// Not allocated, informational only (`INFO`)
+    Info,
+    /// Not allocated, overlay contents (`OVERLAY`)
+    Overlay,
+}
+
+impl SectionKind {
+    pub fn parse(s: &[u8]) -> IResult<&[u8], Self, ErrorTree<&[u8]>> {
+        context(
+            "section type",
+            delimited(
+                tuple((tag("("), space0)),
+                alt((
+                    value(SectionKind::NoLoad, tag("NOLOAD")),
+                    value(SectionKind::ReadOnly, tag("READONLY")),
+                    value(SectionKind::DSect, tag("DSECT")),
+                    value(SectionKind::Copy, tag("COPY")),
+                    value(SectionKind::Info, tag("INFO")),
+                    value(SectionKind::Overlay, tag("OVERLAY")),
+                )),
+                tuple((space0, tag(")"))),
+            )
+        )(s)
+    }
+
+    /// Check whether sections of this type are allocated in the target memory
+    pub fn is_alloc(&self) -> bool {
+        matches!(self, SectionKind::NoLoad | SectionKind::ReadOnly)
+    }
+}
+
+/// Overlay group, output sections sharing a runtime address with separate load addresses
+#[derive(Clone, PartialEq, Debug)]
+pub struct Overlay<'a> {
+    pub addr: u64,
+    /// Overlay member section names, in map order
+    pub sections: Vec<Name<'a>>,
+}
+
+/// Output section class, inferred from section and input section names
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SectionClass {
+    /// Allocated code (ie. `.text`)
+    Code,
+    /// Allocated initialised or read-only data (ie. `.data`, `.rodata`)
+    Data,
+    /// Allocated zero-initialised data (ie. `.bss`)
+    ZeroInit,
+    /// Non-allocated metadata (ie. `.comment`, `.ARM.attributes`)
+    Metadata,
+    /// Non-allocated debug information (ie. `.debug_info`)
+    Debug,
+}
+
+/// Debug information section prefixes, shared by output and input section classification
+pub(crate) const DEBUG_SECTIONS: &[&str] = &[".debug", ".zdebug", ".stab", ".line", ".gnu.debuglto_"];
+/// Zero-initialised section names, matched with [`Name::matches_section`]
+pub(crate) const ZERO_INIT_SECTIONS: &[&str] = &[".bss", ".sbss", ".tbss", "COMMON"];
+/// Code section names, matched with [`Name::matches_section`]
+pub(crate) const CODE_SECTIONS: &[&str] = &[".text", ".init", ".fini", ".plt", ".iplt", ".glue_7", ".vfp11_veneer", ".v4_bx"];
+
+/// Check whether a section name has a debug information prefix (ie. `.debug_info`, `.stab`)
+pub(crate) fn is_debug_section(name: Name) -> bool {
+    DEBUG_SECTIONS.iter().any(|p| name.starts_with(p) )
+}
+
+impl SectionClass {
+    /// Classify a section by name, where this is conclusive
+    pub fn from_name(name: Name) -> Option<Self> {
+        const METADATA: &[&str] = &[
+            ".comment", ".ARM.attributes", ".riscv.attributes", ".gnu.attributes", ".gnu_debuglink",
+            ".symtab", ".strtab", ".shstrtab", ".GCC.command.line", ".note.GNU-stack",
+        ];
+
+        if is_debug_section(name) {
+            Some(SectionClass::Debug)
+        } else if METADATA.iter().any(|m| name == *m ) {
+            Some(SectionClass::Metadata)
+        } else if name.matches_section(ZERO_INIT_SECTIONS) {
+            Some(SectionClass::ZeroInit)
+        } else if name.matches_section(CODE_SECTIONS) {
+            Some(SectionClass::Code)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether sections of this class are allocated in the target memory
+    pub fn is_alloc(&self) -> bool {
+        !matches!(self, SectionClass::Metadata | SectionClass::Debug)
+    }
+}
 
-    pub sections: Vec<Object<'a>>,
+/// Section header fields (name, address, size, load address, type)
+type SectionHeader<'a> = (Name<'a>, Option<u64>, Option<u64>, Option<u64>, Option<SectionKind>);
+
+/// Entry within an output section
+#[derive(Clone, PartialEq, Debug)]
+pub enum Entry<'a> {
+    /// Input section object
+    Object(Object<'a>),
+    /// Padding between input sections
+    Fill(Fill),
+    /// Linker script symbol assignment
+    Assignment(Assignment<'a>),
+    /// Location counter move (ie. `. = ALIGN (0x4)`, `. += 0x100`)
+    Move(Assignment<'a>),
+    /// Data statement (ie. `LONG 0x0`)
+    Data(Data<'a>),
+    /// Fill pattern change (` FILL mask 0xff`)
+    FillMask(Vec<u8>),
+    /// Linker script assertion
+    Assert(Assert<'a>),
+}
+
+/// Lines within an output section
+#[derive(Clone, PartialEq, Debug)]
+enum Item<'a> {
+    Rule(InputSpec<'a>),
+    Entry(Entry<'a>),
 }
 
 impl <'a> Section<'a> {
-    pub fn parse(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
 
-        let (o, (header, body)) = context(
+        let (o, (_, header, items)) = context(
             "section",
             tuple((
+                many0(line_ending),
                 // Memory header (name, location, size), all but first section
                 opt(Self::parse_section_header),
                 // Memory contents (text), all indented
-                many0(Object::parse),
+                parse_items(alt((
+                    map(Fill::parse, |f| Item::Entry(Entry::Fill(f)) ),
+                    map(Fill::parse_mask, |m| Item::Entry(Entry::FillMask(m)) ),
+                    map(Data::parse, |d| Item::Entry(Entry::Data(d)) ),
+                    map(Assert::parse, |a| Item::Entry(Entry::Assert(a)) ),
+                    map(Assignment::parse, |a| match a.is_location_counter() {
+                        true => Item::Entry(Entry::Move(a)),
+                        false => Item::Entry(Entry::Assignment(a)),
+                    }),
+                    map(Self::parse_rule, Item::Rule),
+                    map(Object::parse, |o| Item::Entry(Entry::Object(o)) ),
+                )), is_section_end),
             ))
         )(s)?;
 
-        println!("SECTION body: {:#08x?}, remainder: {}", body, o);
+        // Sections must contain _something_ to avoid matching empty lines forever
+        if header.is_none() && items.is_empty() {
+            // Report malformed headers starting with a name and address (ie. `.text 0x0800000g`),
+            // other lines are reported as unrecognised by the caller
+            let (h, _) = many0(line_ending::<_, ErrorTree<&[u8]>>)(s)?;
+            let starts_header = tuple((
+                parse_path,
+                opt(preceded(space1, SectionKind::parse)),
+                parse_column_sep,
+                tag("0x"),
+            ))(h).is_ok();
+            if starts_header {
+                context("section", Self::parse_section_header)(h)?;
+            }
+            let e = ErrorTree::from_error_kind(h, ErrorKind::Verify);
+            return Err(nom::Err::Error(ErrorTree::add_context(h, "section", e)));
+        }
+
+        // Attach objects to the preceding input section rule
+        let mut rules = vec![];
+        let mut entries = vec![];
+        for i in items {
+            match i {
+                Item::Rule(r) => rules.push(r),
+                Item::Entry(Entry::Object(mut o)) => {
+                    o.rule = rules.len().checked_sub(1);
+                    entries.push(Entry::Object(o));
+                },
+                Item::Entry(e) => entries.push(e),
+            }
+        }
+
+        trace!("section: {:?}, {} rules, {} entries", header, rules.len(), entries.len());
 
         Ok((o, Self{
             name: header.map(|h| h.0 ),
-            addr: header.map(|h| h.1 ),
-            size: header.map(|h| h.2 ),
-            sections: vec![],
+            addr: header.and_then(|h| h.1 ),
+            size: header.and_then(|h| h.2 ),
+            load_addr: header.and_then(|h| h.3 ),
+            kind: header.and_then(|h| h.4 ),
+            rules,
+            entries,
         }))
     }
 
-    fn parse_section_header(s: &str) -> IResult<&str, (&str, u64, u64), ErrorTree<&str>> {
-        let (o, (name, _, addr, _, size, _)) = tuple((
+    fn parse_section_header(s: &[u8]) -> IResult<&[u8], SectionHeader<'_>, ErrorTree<&[u8]>> {
+        alt((
+            Self::parse_placed_header,
+            // Empty or discarded sections (ie. `/DISCARD/`) are listed by name only,
+            // followed by their rules, a blank line or the end of the block
+            map(
+                tuple((
+                    parse_path,
+                    eol,
+                    peek(alt((
+                        line_ending,
+                        eof,
+                        recognize(preceded(space1, InputSpec::parse)),
+                    ))),
+                )),
+                |(name, _, _)| (name, None, None, None, None),
+            ),
+        ))(s)
+    }
+
+    fn parse_placed_header(s: &[u8]) -> IResult<&[u8], SectionHeader<'_>, ErrorTree<&[u8]>> {
+        let (o, (name, kind, _, addr, _, size, load_addr, trailing_kind, _)) = tuple((
             parse_path, // name (ie. `.flash`)
-            space1,
+            opt(preceded(space1, SectionKind::parse)), // type (ie. `(NOLOAD)`)
+            parse_column_sep,
             parse_hex,  // address
             space1,
             parse_hex,  // size (used?)
-            line_ending,
+            opt(preceded(
+                tuple((space1, tag("load address"), space1)),
+                parse_hex, // load address (ie. for initialised data)
+            )),
+            opt(preceded(space1, SectionKind::parse)), // type, where following the header
+            eol,
         ))(s)?;
-    
-        Ok((o, (name, addr, size)))
+
+        Ok((o, (name, Some(addr), Some(size), load_addr, kind.or(trailing_kind))))
+    }
+
+    /// Fetch the load address of this section, defaulting to the virtual address
+    pub fn lma(&self) -> Option<u64> {
+        self.load_addr.or(self.addr)
+    }
+
+    /// Classify this section by type and name, or by the input sections it contains
+    /// (ie. `.flash` containing `.text.*` is code)
+    pub fn class(&self) -> SectionClass {
+        match self.kind {
+            Some(SectionKind::NoLoad) => return SectionClass::ZeroInit,
+            Some(k) if !k.is_alloc() => return SectionClass::Metadata,
+            _ => (),
+        }
+
+        if let Some(c) = self.name.and_then(SectionClass::from_name) {
+            return c;
+        }
+
+        let inputs: Vec<_> = self.objects()
+            .filter_map(|o| o.name.map(|n| SectionClass::from_name(n).unwrap_or(SectionClass::Data) ) )
+            .collect();
+
+        // Sections only reserving space (ie. `._user_heap_stack`) have no contents to load
+        let reserved = self.objects().next().is_none()
+            && !self.entries.iter().any(|e| matches!(e, Entry::Data(_)) );
+
+        if reserved {
+            SectionClass::ZeroInit
+        } else if inputs.contains(&SectionClass::Code) {
+            SectionClass::Code
+        } else if !inputs.is_empty() && inputs.iter().all(|c| *c == SectionClass::ZeroInit ) {
+            SectionClass::ZeroInit
+        } else if !inputs.is_empty() && inputs.iter().all(|c| !c.is_alloc() ) {
+            inputs[0]
+        } else {
+            SectionClass::Data
+        }
+    }
+
+    /// Check whether this section is allocated in the target memory
+    pub fn is_alloc(&self) -> bool {
+        self.class().is_alloc()
+    }
+
+    /// Parse an input section rule line (ie. ` *(.vector_table)`)
+    fn parse_rule(s: &'a [u8]) -> IResult<&'a [u8], InputSpec<'a>, ErrorTree<&'a [u8]>> {
+        delimited(
+            tuple((many0(line_ending), space1)),
+            InputSpec::parse,
+            eol,
+        )(s)
+    }
+
+    /// Fetch the input section objects contained in this section
+    pub fn objects(&self) -> impl Iterator<Item=&Object<'a>> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Object(o) => Some(o),
+            _ => None,
+        })
+    }
+
+    /// Fetch the padding contained in this section
+    pub fn fills(&self) -> impl Iterator<Item=&Fill> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Fill(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    /// Fetch the symbol assignments contained in this section
+    pub fn assignments(&self) -> impl Iterator<Item=&Assignment<'a>> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Assignment(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Fetch the location counter moves contained in this section
+    pub fn moves(&self) -> impl Iterator<Item=&Assignment<'a>> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Move(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Fetch the data statements contained in this section
+    pub fn data(&self) -> impl Iterator<Item=&Data<'a>> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Data(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// Fetch the assertions contained in this section
+    pub fn asserts(&self) -> impl Iterator<Item=&Assert<'a>> {
+        self.entries.iter().filter_map(|e| match e {
+            Entry::Assert(a) => Some(a),
+            _ => None,
+        })
+    }
+
+    /// Compute the total size of the objects, padding and data statements in this section
+    pub fn contents_size(&self) -> u64 {
+        let objects: u64 = self.objects().filter_map(|o| o.size ).sum();
+        let data: u64 = self.data().map(|d| d.size ).sum();
+        objects + self.padding() + data
+    }
+
+    /// Fetch the symbols contained in this section
+    pub fn symbols(&self) -> impl Iterator<Item=&Symbol<'a>> {
+        self.objects().flat_map(|o| o.symbols.iter() )
+    }
+
+    /// Compute the total bytes saved by linker relaxation in this section
+    pub fn relaxation_savings(&self) -> u64 {
+        self.objects().map(|o| o.relaxation_savings() ).sum()
+    }
+
+    /// Compute the total padding in this section
+    pub fn padding(&self) -> u64 {
+        self.fills().map(|f| f.size ).sum()
+    }
+
+    /// Fetch the input section rule that placed the provided object
+    pub fn rule(&self, object: &Object) -> Option<&InputSpec<'a>> {
+        object.rule.and_then(|i| self.rules.get(i) )
     }
 }
 
-/// Code section in application binary
+/// Input section object (ie. `.text.main`) within an output section
 #[derive(Clone, PartialEq, Debug)]
 pub struct Object<'a> {
-    pub name: Option<&'a str>,
-    
+    pub name: Option<Name<'a>>,
+
     pub addr: Option<u64>,
     pub size: Option<u64>,
-    pub source: Option<&'a str>,
+    pub source: Option<InputFile<'a>>,
+    /// Size prior to linker relaxation, where this differs from `size`
+    pub pre_relax_size: Option<u64>,
+
+    /// Index of the input section rule in [`Section::rules`] that placed this object
+    pub rule: Option<usize>,
 
     pub symbols: Vec<Symbol<'a>>,
 }
 
+/// Object header fields (name, address, size, source file)
+type ObjectHeader<'a> = (Name<'a>, u64, u64, Option<InputFile<'a>>);
 
 impl <'a> Object<'a> {
-    pub fn parse(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
-        // Sections (except the first) _should_ start with a 
-        // name (`.something`) and no indent, with nested sections and symbols indented below this
-        let (o, (label, header, symbols)) = context(
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        // Objects _should_ start with an input section header (` .text  0x... 0x... file`),
+        // with symbols indented below this
+        let (o, (_, header, pre_relax_size, symbols)) = context(
             "object",
             tuple((
-                // Object section label (ie. `*(.vector_table)`)
-                opt(delimited(space1, parse_path, line_ending)),
+                many0(line_ending),
                 // Section header (name, location, size), all but first section
                 opt(Self::parse_object_header),
+                // Size before relaxing, where applicable
+                opt(parse_pre_relax_size),
                 // Section contents (text)
                 Symbol::parse_many,
             ))
         )(s)?;
 
-        println!("section: '\r\n{}\r\n'", s);
-
-        println!("label: {:#?} header: {:#?}, symbols: {:#?}", label, header, symbols);
+        // Objects must contain _something_ to avoid matching empty lines forever,
+        // reporting why the header did not match where available
+        if header.is_none() && symbols.is_empty() {
+            let e = match context("object", preceded(many0(line_ending), Self::parse_object_header))(s) {
+                Err(e) => e,
+                Ok(_) => nom::Err::Error(ErrorTree::from_error_kind(s, ErrorKind::Verify)),
+            };
+            return Err(e);
+        }
 
+        trace!("object header: {:?}, {} symbols", header, symbols.len());
 
         Ok((
             o,
@@ -98,35 +458,107 @@ impl <'a> Object<'a> {
                 name: header.map(|h| h.0 ),
                 addr: header.map(|h| h.1 ),
                 size: header.map(|h| h.2 ),
-                source: header.map(|h| h.3 ).flatten(),
+                source: header.and_then(|h| h.3 ),
+                pre_relax_size,
+                rule: None,
                 symbols,
             },
         ))
     }
 
+    /// Compute the bytes saved by linker relaxation of this object
+    pub fn relaxation_savings(&self) -> u64 {
+        match (self.pre_relax_size, self.size) {
+            (Some(p), Some(s)) => p.saturating_sub(s),
+            _ => 0,
+        }
+    }
 
-    fn parse_object_header(s: &str) -> IResult<&str, (&str, u64, u64, Option<&str>), ErrorTree<&str>> {
+    fn parse_object_header(s: &[u8]) -> IResult<&[u8], ObjectHeader<'_>, ErrorTree<&[u8]>> {
         let (o, (_, name, _, addr, _, size, file, _)) = tuple((
             space1,
             parse_path, // Section name
-            space1,
+            parse_column_sep,
             parse_hex,  // Section address
             space1,
             parse_hex,  // Section size
-            opt(tuple((
+            opt(preceded(
                 space1,
-                parse_path, // File name
-            ))),
-            line_ending,
+                InputFile::parse, // File name
+            )),
+            eol,
         ))(s)?;
 
-        Ok((o, (name, addr, size, file.map(|v| v.1 ))))
+        Ok((o, (name, addr, size, file)))
+    }
+}
+
+/// Check for the end of an output section, at the end of the block or the next unindented line
+fn is_section_end(s: &[u8]) -> bool {
+    is_block_end(s) || !s[0].is_ascii_whitespace()
+}
+
+/// Padding inserted between input sections (`*fill*`)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fill {
+    pub addr: u64,
+    pub size: u64,
+    /// Fill pattern bytes, where specified
+    pub pattern: Option<Vec<u8>>,
+}
+
+impl Fill {
+    pub fn parse(s: &[u8]) -> IResult<&[u8], Self, ErrorTree<&[u8]>> {
+        let (o, (_, _, _, _, addr, _, size, pattern, _)) = context(
+            "fill",
+            tuple((
+                many0(line_ending),
+                space1,
+                tag("*fill*"),
+                space1,
+                parse_hex,  // Fill address
+                space1,
+                parse_hex,  // Fill size
+                opt(preceded(
+                    space1,
+                    Self::parse_pattern,
+                )),
+                eol,
+            ))
+        )(s)?;
+
+        Ok((o, Self{ addr, size, pattern }))
+    }
+
+    /// Parse a fill pattern statement (` FILL mask 0xff`)
+    pub fn parse_mask(s: &[u8]) -> IResult<&[u8], Vec<u8>, ErrorTree<&[u8]>> {
+        context(
+            "fill mask",
+            delimited(
+                tuple((many0(line_ending), space1, tag("FILL mask 0x"))),
+                Self::parse_pattern,
+                eol,
+            )
+        )(s)
+    }
+
+    /// Parse fill pattern bytes (ie. `ff` or `00000000`)
+    fn parse_pattern(s: &[u8]) -> IResult<&[u8], Vec<u8>, ErrorTree<&[u8]>> {
+        map_res(
+            take_while1(is_hex_digit),
+            |v: &[u8]| {
+                v.chunks(2)
+                    .map(|c| from_hex(c).map(|b| b as u8) )
+                    .collect::<Result<Vec<_>, _>>()
+            },
+        )(s)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::objects::{SymbolKind, Pattern, FilePath};
 
     use pretty_assertions::assert_eq;
 
@@ -135,25 +567,26 @@ mod tests {
 
         let sections = &[(
             Object{
-                name: Some(".vendorheader"),
+                name: Some(Name::new(".vendorheader")),
                 addr: Some(0x0000000008040000),
                 size: Some(0xa00),
-                source: Some("build/firmware/embed/firmware/vendorheader.o"),
+                source: Some(InputFile::Path(FilePath::new("build/firmware/embed/firmware/vendorheader.o"))),
+                pre_relax_size: None,
+                rule: None,
                 symbols: vec![
                     Symbol{
                         name: None,
                         addr: 0x0000000008040000,
-                        kind: SymbolKind::Value("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_start"),
+                        kind: SymbolKind::Value(Name::new("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_start")),
                     },
                     Symbol{
                         name: None,
                         addr: 0x0000000008040a00,
-                        kind: SymbolKind::Value("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end"),
+                        kind: SymbolKind::Value(Name::new("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end")),
                     },
                 ],
             },
 "
-  *(.vendorheader)
   .vendorheader  0x0000000008040000      0xa00 build/firmware/embed/firmware/vendorheader.o
                  0x0000000008040000                _binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_start
                  0x0000000008040a00                _binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end
@@ -164,56 +597,36 @@ mod tests {
                 addr: None,
                 size: None,
                 source: None,
+                pre_relax_size: None,
+                rule: None,
                 symbols: vec![
                     Symbol{
                         name: None,
-                        addr: 0x0000000020030000,
-                        kind: SymbolKind::Value("main_stack_base = (ORIGIN (SRAM) + LENGTH (SRAM))"),
+                        addr: 0x0000000020000000,
+                        kind: SymbolKind::Value(Name::new("_sdata")),
                     },
                     Symbol{
                         name: None,
-                        addr: 0x0000000020030000,
-                        kind: SymbolKind::Value("_estack = main_stack_base"),
+                        addr: 0x0000000020000200,
+                        kind: SymbolKind::Value(Name::new("_edata")),
                     }
                 ],
             },
             "
-            0x0000000020030000                main_stack_base = (ORIGIN (SRAM) + LENGTH (SRAM))
-            0x0000000020030000                _estack = main_stack_base
+            0x0000000020000000                _sdata
+            0x0000000020000200                _edata
             "
         ), (
             Object{
-                name: Some(".flash2"),
+                name: Some(Name::new(".rodata.str1.1")),
                 addr: Some(0x0000000008120000),
-                size: Some(0x62a00),
-                source: Some("build/firmware/frozen_mpy.o(.rodata*)"),
-                symbols: vec![
-                    Symbol{
-                        name: Some(".rodata.str1.1"),
-                        addr: 0x0000000008120000,
-                        kind: SymbolKind::Object{
-                            size: 0xf9d8,
-                            source: Some("0xf9d8 build/firmware/frozen_mpy.o"),
-                        },
-                    }, Symbol{
-                        name: Some(".rodata"),
-                        addr: 0x000000000812f9d8,
-                        kind: SymbolKind::Object{
-                            size: 0x91e1,
-                            source: Some("build/firmware/frozen_mpy.o"),
-                        },
-                    }, Symbol{
-                        name: Some("*fill*"),
-                        addr: 0x0000000008138bb9,
-                        kind: SymbolKind::Object{
-                            size: 0x1,
-                            source: None,
-                        },
-                    }
-                ],
+                size: Some(0xf9d8),
+                source: Some(InputFile::Path(FilePath::new("build/firmware/frozen_mpy.o"))),
+                pre_relax_size: Some(0xff3c),
+                rule: None,
+                symbols: vec![],
             },
-" build/firmware/frozen_mpy.o(.rodata*)
- .rodata.str1.1
+" .rodata.str1.1
                 0x0000000008120000     0xf9d8 build/firmware/frozen_mpy.o
                                        0xff3c (size before relaxing)
  .rodata        0x000000000812f9d8     0x91e1 build/firmware/frozen_mpy.o
@@ -222,8 +635,257 @@ mod tests {
 
 
         for (v, raw) in sections {
-            let (_, p) = Object::parse(raw).unwrap();
+            let (_, p) = Object::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+        }
+    }
+
+    #[test]
+    fn parse_object_sources() {
+        let sources = &[
+            (
+                Some(InputFile::Member{ archive: FilePath::new("build/libtrezor_lib.a"), member: Name::new("core-1.o") }),
+                " .text          0x0000000008041000       0x40 build/libtrezor_lib.a(core-1.o)\n",
+            ),
+            (Some(InputFile::Linker(Name::new("linker stubs"))), " .glue_7        0x0000000008041040        0x0 linker stubs\n"),
+            (None, " .text          0x0000000008041040        0x0\n"),
+        ];
+
+        for (v, raw) in sources {
+            let (_, p) = Object::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p.source, v);
+        }
+    }
+
+    #[test]
+    fn parse_sections() {
+        let raw = "
+.flash2         0x0000000008120000    0x62a00
+ build/firmware/frozen_mpy.o(.rodata*)
+ .rodata.str1.1
+                0x0000000008120000     0xf9d8 build/firmware/frozen_mpy.o
+                                       0xff3c (size before relaxing)
+ .rodata        0x000000000812f9d8     0x91e1 build/firmware/frozen_mpy.o
+ *fill*         0x0000000008138bb9        0x1
+
+.flash          0x0000000008040e00    0xbd000
+";
+
+        let v = Section{
+            name: Some(Name::new(".flash2")),
+            addr: Some(0x0000000008120000),
+            size: Some(0x62a00),
+            load_addr: None,
+            kind: None,
+            rules: vec![
+                InputSpec{
+                    keep: false,
+                    file: Pattern{ name: Name::new("build/firmware/frozen_mpy.o"), sort: vec![], exclude: vec![] },
+                    sections: vec![Pattern{ name: Name::new(".rodata*"), sort: vec![], exclude: vec![] }],
+                },
+            ],
+            entries: vec![
+                Entry::Object(Object{
+                    name: Some(Name::new(".rodata.str1.1")),
+                    addr: Some(0x0000000008120000),
+                    size: Some(0xf9d8),
+                    source: Some(InputFile::Path(FilePath::new("build/firmware/frozen_mpy.o"))),
+                    pre_relax_size: Some(0xff3c),
+                    rule: Some(0),
+                    symbols: vec![],
+                }),
+                Entry::Object(Object{
+                    name: Some(Name::new(".rodata")),
+                    addr: Some(0x000000000812f9d8),
+                    size: Some(0x91e1),
+                    source: Some(InputFile::Path(FilePath::new("build/firmware/frozen_mpy.o"))),
+                    pre_relax_size: None,
+                    rule: Some(0),
+                    symbols: vec![],
+                }),
+                Entry::Fill(Fill{
+                    addr: 0x0000000008138bb9,
+                    size: 0x1,
+                    pattern: None,
+                }),
+            ],
+        };
+
+        let (o, p) = Section::parse(raw.as_bytes()).unwrap();
+        assert_eq!(p, v);
+        assert!(o.trim_ascii_start().starts_with(b".flash "));
+        assert_eq!(p.relaxation_savings(), 0xff3c - 0xf9d8);
+        assert_eq!(p.padding(), 0x1);
+    }
+
+    #[test]
+    fn parse_fills() {
+        let fills = &[
+            (
+                Fill{ addr: 0x0000000008138bb9, size: 0x1, pattern: None },
+                " *fill*         0x0000000008138bb9        0x1 \n",
+            ), (
+                Fill{ addr: 0x0000000008040fad, size: 0x3, pattern: Some(vec![0xff]) },
+                " *fill*         0x0000000008040fad        0x3 ff\n",
+            ), (
+                Fill{ addr: 0x0000000020000004, size: 0x4, pattern: Some(vec![0x00, 0x00, 0x00, 0x00]) },
+                " *fill*         0x0000000020000004        0x4 00000000",
+            ),
+        ];
+
+        for (v, raw) in fills {
+            let (_, p) = Fill::parse(raw.as_bytes()).unwrap();
             assert_eq!(&p, v);
         }
     }
+
+    #[test]
+    fn parse_wrapped_sections() {
+        let raw = "
+.ARM.attributes
+                0x0000000000000000       0x30
+ *(.ARM.attributes)
+ .ARM.attributes
+                0x0000000000000000       0x30 build/firmware/embed/firmware/main.o
+";
+
+        let v = Section{
+            name: Some(Name::new(".ARM.attributes")),
+            addr: Some(0x00),
+            size: Some(0x30),
+            load_addr: None,
+            kind: None,
+            rules: vec![
+                InputSpec{
+                    keep: false,
+                    file: Pattern{ name: Name::new("*"), sort: vec![], exclude: vec![] },
+                    sections: vec![Pattern{ name: Name::new(".ARM.attributes"), sort: vec![], exclude: vec![] }],
+                },
+            ],
+            entries: vec![
+                Entry::Object(Object{
+                    name: Some(Name::new(".ARM.attributes")),
+                    addr: Some(0x00),
+                    size: Some(0x30),
+                    source: Some(InputFile::Path(FilePath::new("build/firmware/embed/firmware/main.o"))),
+                    pre_relax_size: None,
+                    rule: Some(0),
+                    symbols: vec![],
+                }),
+            ],
+        };
+
+        let (_, p) = Section::parse(raw.as_bytes()).unwrap();
+        assert_eq!(p, v);
+    }
+
+    #[test]
+    fn classify_sections() {
+        let names = &[
+            (".text", Some(SectionClass::Code)),
+            (".text.main", Some(SectionClass::Code)),
+            (".init_array", None),
+            (".rodata", None),
+            (".bss.buffer", Some(SectionClass::ZeroInit)),
+            ("COMMON", Some(SectionClass::ZeroInit)),
+            (".debug_info", Some(SectionClass::Debug)),
+            (".comment", Some(SectionClass::Metadata)),
+            (".ARM.attributes", Some(SectionClass::Metadata)),
+        ];
+
+        for (name, class) in names {
+            assert_eq!(&SectionClass::from_name(Name::new(name)), class, "{}", name);
+        }
+
+        // Unknown output sections are classified by their contents
+        let raw = "
+.flash          0x0000000008040e00      0x200
+ *(.text*)
+ .text.main     0x0000000008040e00      0x100 build/main.o
+ .rodata.str1.1 0x0000000008040f00      0x100 build/main.o
+";
+
+        let (_, p) = Section::parse(raw.as_bytes()).unwrap();
+        assert_eq!(p.class(), SectionClass::Code);
+
+        let raw = "
+.ram_bss        0x0000000020000000      0x100
+ .bss.buffer    0x0000000020000000      0x100 build/main.o
+";
+
+        let (_, p) = Section::parse(raw.as_bytes()).unwrap();
+        assert_eq!(p.class(), SectionClass::ZeroInit);
+        assert!(p.is_alloc());
+    }
+
+    #[test]
+    fn parse_load_address() {
+        let raw = "
+.data           0x0000000020000000      0x200 load address 0x00000000080fde08
+ *(.data*)
+ .data          0x0000000020000000      0x200 build/firmware/embed/firmware/main.o
+";
+
+        let (_, p) = Section::parse(raw.as_bytes()).unwrap();
+        assert_eq!(p.name, Some(Name::new(".data")));
+        assert_eq!(p.addr, Some(0x0000000020000000));
+        assert_eq!(p.size, Some(0x200));
+        assert_eq!(p.load_addr, Some(0x00000000080fde08));
+        assert_eq!(p.lma(), Some(0x00000000080fde08));
+    }
+
+    #[test]
+    fn parse_section_assignments() {
+        let raw = "
+.flash          0x0000000008040e00    0xbd000
+ *(.vector_table)
+ .vector_table  0x0000000008040e00      0x1ac build/firmware/embed/trezorhal/vectortable.o
+                0x0000000008040fac                . = ALIGN (0x4)
+                0x0000000008040fac                _text_start = .
+";
+
+        let (_, p) = Section::parse(raw.as_bytes()).unwrap();
+
+        assert_eq!(p.objects().count(), 1);
+        assert_eq!(p.assignments().map(|a| (a.symbol, a.expr, a.value) ).collect::<Vec<_>>(), &[
+            (Name::new("_text_start"), Name::new("."), Some(0x0000000008040fac)),
+        ]);
+        assert_eq!(p.moves().map(|a| a.alignment() ).collect::<Vec<_>>(), &[Some(0x4)]);
+    }
+
+    #[test]
+    fn parse_section_statements() {
+        let raw = "
+.isr_table      0x0000000008040000       0x1c
+                0x0000000008040000                . = ALIGN (0x4)
+ FILL mask 0xff
+ *(.isr_header)
+ .isr_header    0x0000000008040000        0x8 build/firmware/isr.o
+                0x0000000008040000                isr_header
+                0x0000000008040008        0x4 LONG 0x20030000 _estack
+ *fill*         0x000000000804000c        0x4 ff
+                0x0000000008040010                . += 0x4
+                0x0000000008040010        0x8 QUAD 0x0
+                0x0000000008040018        0x4 LONG 0x1
+                0x0000000000000001                ASSERT ((. <= 0x8040100), isr table too large)
+";
+
+        let (_, p) = Section::parse(raw.as_bytes()).unwrap();
+
+        assert_eq!(p.objects().count(), 1);
+        assert_eq!(p.symbols().count(), 1);
+        assert_eq!(p.moves().map(|a| a.expr ).collect::<Vec<_>>(), &[Name::new("ALIGN (0x4)"), Name::new("0x4")]);
+        assert_eq!(p.data().map(|d| (d.addr, d.size, d.value, d.expr) ).collect::<Vec<_>>(), &[
+            (0x0000000008040008, 0x4, 0x20030000, Some(Name::new("_estack"))),
+            (0x0000000008040010, 0x8, 0x0, None),
+            (0x0000000008040018, 0x4, 0x1, None),
+        ]);
+        assert_eq!(p.asserts().map(|a| (a.expr, a.message) ).collect::<Vec<_>>(), &[
+            (Name::new("(. <= 0x8040100)"), Name::new("isr table too large")),
+        ]);
+        assert!(p.entries.contains(&Entry::FillMask(vec![0xff])));
+
+        // Data statements are accounted for in section totals
+        assert_eq!(p.contents_size(), p.size.unwrap());
+    }
 }
@@ -0,0 +1,227 @@
+use nom::{
+    IResult,
+    error::context, sequence::{tuple, delimited, terminated, preceded},
+    bytes::complete::{take_while1, tag},
+    character::complete::{space0, space1},
+    combinator::{opt, map, value}, branch::alt, multi::separated_list1,
+};
+
+use nom_supreme::error::ErrorTree;
+
+use super::Name;
+
+/// Input section selector rule from the linker script (ie. `*(.text*)`, `KEEP (*(.isr_vector))`)
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputSpec<'a> {
+    /// Selected sections are retained with `KEEP`
+    pub keep: bool,
+    /// Input file (or archive) pattern
+    pub file: Pattern<'a>,
+    /// Input section patterns
+    pub sections: Vec<Pattern<'a>>,
+}
+
+/// Wildcard pattern with optional sorting and exclusions
+#[derive(Clone, PartialEq, Debug)]
+pub struct Pattern<'a> {
+    pub name: Name<'a>,
+    /// Sort wrappers, outermost first
+    pub sort: Vec<SortKind>,
+    /// Files excluded with `EXCLUDE_FILE`
+    pub exclude: Vec<Name<'a>>,
+}
+
+/// Wildcard selector fields (file pattern, section patterns)
+type WildSpec<'a> = (Pattern<'a>, Vec<Pattern<'a>>);
+
+/// Sorting applied to matched input sections
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortKind {
+    /// `SORT_BY_NAME` (or `SORT`)
+    Name,
+    /// `SORT_BY_ALIGNMENT`
+    Alignment,
+    /// `SORT_BY_INIT_PRIORITY`
+    InitPriority,
+    /// `SORT_NONE`
+    None,
+}
+
+impl <'a> InputSpec<'a> {
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (keep, file, sections)) = context(
+            "input spec",
+            alt((
+                // Kept sections (ie. `KEEP (*(.isr_vector))`)
+                map(
+                    delimited(
+                        tuple((tag("KEEP"), space0, tag("("), space0)),
+                        Self::parse_wild,
+                        tuple((space0, tag(")"))),
+                    ),
+                    |(file, sections)| (true, file, sections),
+                ),
+                map(Self::parse_wild, |(file, sections)| (false, file, sections) ),
+            ))
+        )(s)?;
+
+        Ok((o, Self{ keep, file, sections }))
+    }
+
+    fn parse_wild(s: &'a [u8]) -> IResult<&'a [u8], WildSpec<'a>, ErrorTree<&'a [u8]>> {
+        let (o, (exclude, mut file, sections)) = tuple((
+            // File exclusions (ie. `EXCLUDE_FILE(*crtend.o) *(.dtors)`)
+            opt(terminated(parse_exclude, space0)),
+            Pattern::parse,
+            delimited(
+                tuple((space0, tag("("), space0)),
+                separated_list1(space1, Pattern::parse),
+                tuple((space0, tag(")"))),
+            ),
+        ))(s)?;
+
+        if let Some(mut e) = exclude {
+            e.append(&mut file.exclude);
+            file.exclude = e;
+        }
+
+        Ok((o, (file, sections)))
+    }
+}
+
+impl <'a> Pattern<'a> {
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        context(
+            "pattern",
+            alt((
+                // Sorted pattern (ie. `SORT_BY_NAME(.init_array.*)`), may be nested
+                map(
+                    tuple((
+                        SortKind::parse,
+                        delimited(
+                            tuple((space0, tag("("), space0)),
+                            Pattern::parse,
+                            tuple((space0, tag(")"))),
+                        ),
+                    )),
+                    |(kind, mut p)| { p.sort.insert(0, kind); p },
+                ),
+                // Plain pattern with optional exclusions (ie. `EXCLUDE_FILE(*crtend.o) .ctors`)
+                map(
+                    tuple((
+                        opt(terminated(parse_exclude, space0)),
+                        parse_name,
+                    )),
+                    |(exclude, name)| Self{ name, sort: vec![], exclude: exclude.unwrap_or_default() },
+                ),
+            ))
+        )(s)
+    }
+}
+
+impl SortKind {
+    pub fn parse(s: &[u8]) -> IResult<&[u8], Self, ErrorTree<&[u8]>> {
+        alt((
+            value(SortKind::Name, tag("SORT_BY_NAME")),
+            value(SortKind::Alignment, tag("SORT_BY_ALIGNMENT")),
+            value(SortKind::InitPriority, tag("SORT_BY_INIT_PRIORITY")),
+            value(SortKind::None, tag("SORT_NONE")),
+            value(SortKind::Name, tag("SORT")),
+        ))(s)
+    }
+}
+
+/// Parse an `EXCLUDE_FILE(a b c)` list
+fn parse_exclude(s: &[u8]) -> IResult<&[u8], Vec<Name<'_>>, ErrorTree<&[u8]>> {
+    preceded(
+        tuple((tag("EXCLUDE_FILE"), space0, tag("("), space0)),
+        terminated(
+            separated_list1(space1, parse_name),
+            tuple((space0, tag(")"))),
+        ),
+    )(s)
+}
+
+/// Parse a (wildcard) file or section name
+fn parse_name(s: &[u8]) -> IResult<&[u8], Name<'_>, ErrorTree<&[u8]>> {
+    map(take_while1(|c: u8| !c.is_ascii_whitespace() && c != b'(' && c != b')'), Name)(s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    fn pattern(name: &str) -> Pattern<'_> {
+        Pattern{ name: Name::new(name), sort: vec![], exclude: vec![] }
+    }
+
+    #[test]
+    fn parse_input_specs() {
+        let specs = &[
+            (
+                InputSpec{ keep: false, file: pattern("*"), sections: vec![pattern(".text*")] },
+                "*(.text*)",
+            ), (
+                InputSpec{ keep: true, file: pattern("*"), sections: vec![pattern(".isr_vector")] },
+                "KEEP (*(.isr_vector))",
+            ), (
+                InputSpec{
+                    keep: false,
+                    file: pattern("build/firmware/frozen_mpy.o"),
+                    sections: vec![pattern(".rodata*")],
+                },
+                "build/firmware/frozen_mpy.o(.rodata*)",
+            ), (
+                InputSpec{ keep: false, file: pattern("*"), sections: vec![pattern(".text"), pattern(".text.*")] },
+                "*(.text .text.*)",
+            ), (
+                InputSpec{
+                    keep: true,
+                    file: pattern("*"),
+                    sections: vec![Pattern{ name: Name::new(".init_array.*"), sort: vec![SortKind::Name], exclude: vec![] }],
+                },
+                "KEEP (*(SORT_BY_NAME(.init_array.*)))",
+            ), (
+                InputSpec{
+                    keep: false,
+                    file: pattern("*"),
+                    sections: vec![Pattern{
+                        name: Name::new(".data*"),
+                        sort: vec![SortKind::Name, SortKind::Alignment],
+                        exclude: vec![],
+                    }],
+                },
+                "*(SORT_BY_NAME(SORT_BY_ALIGNMENT(.data*)))",
+            ), (
+                InputSpec{
+                    keep: false,
+                    file: Pattern{ name: Name::new("*"), sort: vec![], exclude: vec![Name::new("*crtend.o")] },
+                    sections: vec![pattern(".ctors")],
+                },
+                "EXCLUDE_FILE(*crtend.o) *(.ctors)",
+            ), (
+                InputSpec{
+                    keep: false,
+                    file: pattern("*"),
+                    sections: vec![Pattern{ name: Name::new(".dtors"), sort: vec![], exclude: vec![Name::new("*crtend.o"), Name::new("*crtend?.o")] }],
+                },
+                "*(EXCLUDE_FILE(*crtend.o *crtend?.o) .dtors)",
+            ), (
+                InputSpec{
+                    keep: false,
+                    file: Pattern{ name: Name::new("*"), sort: vec![SortKind::Name], exclude: vec![] },
+                    sections: vec![pattern(".ctors")],
+                },
+                "SORT(*)(.ctors)",
+            ),
+        ];
+
+        for (v, raw) in specs {
+            let (o, p) = InputSpec::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+            assert!(o.is_empty());
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use nom::{
+    IResult,
+    error::context, sequence::{tuple, preceded, terminated},
+    bytes::complete::tag,
+    character::complete::{line_ending, not_line_ending, space0, space1},
+    combinator::{map_opt, opt, value}, branch::alt, multi::many0,
+};
+
+use nom_supreme::error::ErrorTree;
+
+use crate::{parse_hex, eol};
+use super::{Assignment, Name};
+
+/// Data statement within an output section (ie. `LONG 0x0`)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Data<'a> {
+    pub addr: u64,
+    pub size: u64,
+    pub kind: DataKind,
+    /// Value written
+    pub value: u64,
+    /// Source expression, where this is not a constant (ie. `_sdata`)
+    pub expr: Option<Name<'a>>,
+}
+
+/// Data statement width
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DataKind {
+    Byte,
+    Short,
+    Long,
+    Quad,
+    Squad,
+}
+
+impl DataKind {
+    pub fn parse(s: &[u8]) -> IResult<&[u8], Self, ErrorTree<&[u8]>> {
+        alt((
+            value(DataKind::Byte, tag("BYTE")),
+            value(DataKind::Short, tag("SHORT")),
+            value(DataKind::Long, tag("LONG")),
+            value(DataKind::Squad, tag("SQUAD")),
+            value(DataKind::Quad, tag("QUAD")),
+        ))(s)
+    }
+
+    /// Fetch the size of the data written, in bytes
+    pub fn size(&self) -> u64 {
+        match self {
+            DataKind::Byte => 1,
+            DataKind::Short => 2,
+            DataKind::Long => 4,
+            DataKind::Quad | DataKind::Squad => 8,
+        }
+    }
+}
+
+impl <'a> Data<'a> {
+    /// Parse a data statement line (ie. `  0x0000000008040fac        0x4 LONG 0x0`)
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (_, _, addr, _, mut d)) = context(
+            "data",
+            tuple((
+                many0(line_ending),
+                space1,
+                parse_hex,  // Address
+                space1,
+                Self::parse_statement,
+            ))
+        )(s)?;
+
+        d.addr = addr;
+
+        Ok((o, d))
+    }
+
+    /// Parse a data statement following the address column (ie. `0x4 LONG 0x0 _sdata`)
+    pub fn parse_statement(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (size, _, kind, _, value, expr, _)) = tuple((
+            parse_hex,  // Size
+            space1,
+            DataKind::parse,
+            space1,
+            parse_hex,  // Value
+            opt(preceded(
+                space1,
+                map_opt(not_line_ending, |v: &'a [u8]| Some(Name(v.trim_ascii_end())).filter(|v| !v.is_empty() ) ),
+            )),
+            eol,
+        ))(s)?;
+
+        Ok((o, Self{ addr: 0, size, kind, value, expr }))
+    }
+}
+
+/// Linker script assertion (ie. `ASSERT ((_estack > 0x20000000), stack overflow)`)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Assert<'a> {
+    /// Asserted condition
+    pub expr: Name<'a>,
+    /// Message reported on failure
+    pub message: Name<'a>,
+    /// Resulting condition value, where resolved by the linker
+    pub value: Option<u64>,
+}
+
+impl <'a> Assert<'a> {
+    /// Parse an assertion line (ie. `  0x0000000000000001  ASSERT ((. <= 0x1000), too large)`)
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        let (o, (_, _, value, _, mut a)) = context(
+            "assert",
+            tuple((
+                many0(line_ending),
+                space1,
+                Assignment::parse_value,
+                space1,
+                terminated(Self::parse_statement, eol),
+            ))
+        )(s)?;
+
+        a.value = value;
+
+        Ok((o, a))
+    }
+
+    /// Parse an assertion statement (ie. `ASSERT ((. <= 0x1000), too large)`)
+    pub fn parse_statement(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
+        map_opt(
+            preceded(
+                tuple((tag("ASSERT"), space0, tag("("))),
+                not_line_ending,
+            ),
+            |v: &'a [u8]| {
+                let v = v.trim_ascii_end().strip_suffix(b")")?;
+                let (expr, message) = split_args(v)?;
+                Some(Self{ expr: Name(expr.trim_ascii()), message: Name(message.trim_ascii()), value: None })
+            },
+        )(s)
+    }
+}
+
+/// Split arguments at the first unbracketed comma
+fn split_args(s: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut depth = 0usize;
+    for (i, c) in s.iter().enumerate() {
+        match c {
+            b'(' => depth += 1,
+            b')' => depth = depth.checked_sub(1)?,
+            b',' if depth == 0 => return Some((&s[..i], &s[i+1..])),
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_data() {
+        let data = &[
+            (
+                Data{ addr: 0x0000000008040fac, size: 0x4, kind: DataKind::Long, value: 0x0, expr: None },
+                "                0x0000000008040fac        0x4 LONG 0x0",
+            ), (
+                Data{ addr: 0x0000000020000000, size: 0x1, kind: DataKind::Byte, value: 0xff, expr: None },
+                "                0x0000000020000000        0x1 BYTE 0xff\n",
+            ), (
+                Data{
+                    addr: 0x0000000008040fb0,
+                    size: 0x8,
+                    kind: DataKind::Squad,
+                    value: 0x20000000,
+                    expr: Some(Name::new("_sdata")),
+                },
+                "                0x0000000008040fb0        0x8 SQUAD 0x20000000 _sdata",
+            ),
+        ];
+
+        for (v, raw) in data {
+            let (_, p) = Data::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+            assert_eq!(p.size, p.kind.size());
+        }
+    }
+
+    #[test]
+    fn parse_asserts() {
+        let asserts = &[
+            (
+                Assert{ expr: Name::new("(_estack > 0x20000000)"), message: Name::new("stack overflow"), value: Some(0x1) },
+                "                0x0000000000000001                ASSERT ((_estack > 0x20000000), stack overflow)",
+            ), (
+                Assert{ expr: Name::new("(SIZEOF (.heap) >= 0x400)"), message: Name::new("heap too small, check config"), value: None },
+                "                *undef*                           ASSERT ((SIZEOF (.heap) >= 0x400), heap too small, check config)\n",
+            ),
+        ];
+
+        for (v, raw) in asserts {
+            let (_, p) = Assert::parse(raw.as_bytes()).unwrap();
+            assert_eq!(&p, v);
+        }
+    }
+}
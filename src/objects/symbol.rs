@@ -1,62 +1,85 @@
 
 use nom::{
     IResult,
-    error::context, sequence::{tuple, preceded}, 
-    bytes::complete::{take_while1, tag, take_until1}, 
-    character::complete::{line_ending, space1, space0, multispace0, one_of, newline},
-    combinator::{opt, map}, branch::alt, multi::{many1, many0},
+    error::context, sequence::{tuple, delimited},
+    bytes::complete::take_while1,
+    character::complete::{line_ending, space1, space0, newline},
+    combinator::{opt, map, not, success}, branch::alt, multi::many0,
 };
 
 use nom_supreme::error::ErrorTree;
 
-use crate::{parse_hex, parse_path};
+use log::trace;
+
+use crate::{parse_hex, parse_path, parse_pre_relax_size, eol};
+use super::{Assignment, Data, Assert, InputFile, Name};
 
 /// A symbol included in the application binary
 #[derive(Clone, PartialEq, Debug)]
 pub struct Symbol<'a> {
-    pub name: Option<&'a str>,
+    pub name: Option<Name<'a>>,
     pub addr: u64,
     pub kind: SymbolKind<'a>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum SymbolKind<'a> {
-    Value(&'a str),
+    Value(Name<'a>),
     Object{
         size: u64,
-        source: Option<&'a str>,
+        /// Size prior to linker relaxation, where this differs from `size`
+        pre_relax_size: Option<u64>,
+        source: Option<InputFile<'a>>,
     },
 }
 
 impl <'a> Symbol<'a> {
-    pub fn parse(s: &'a str) -> IResult<&'a str, Self, ErrorTree<&'a str>> {
-
-        println!("PARSE: '{}'", s);
+    pub fn parse(s: &'a [u8]) -> IResult<&'a [u8], Self, ErrorTree<&'a [u8]>> {
 
         // Start by parsing the first line of the object
-        let (mut o, (indent, addr, _, kind)) = context(
+        let (mut o, (wrapped, indent, addr, _, mut kind)) = context(
             "symbol",
-            tuple((
-                get_indent,
-                parse_hex,              // Address
-                space1,
-                // From here we either have a value or a size + file
-                alt((
-                    map(tuple((
-                        parse_hex,       // Size
-                        space0,
-                        opt(parse_path), // File path (TODO: may be on next line)
-                        line_ending,
-                    )), |(size, _, source, _)| SymbolKind::Object{ size, source } ),
-
-                    map(tuple((
-                        take_while1(|c| c != '\r' && c != '\n'),
-                    )), |v| SymbolKind::Value(v.0) ),
+            alt((
+                // Long object names are wrapped, with the address, size and file on the next line
+                tuple((
+                    map(Self::parse_wrapped_name, Some),
+                    get_indent,
+                    parse_hex,              // Address
+                    space1,
+                    Self::parse_object,
+                )),
+                tuple((
+                    success(None),
+                    get_indent,
+                    parse_hex,              // Address
+                    space1,
+                    // From here we either have a value or a size + file
+                    alt((
+                        Self::parse_object,
+                        map(tuple((
+                            // Linker script assignments are parsed separately
+                            not(Assignment::parse_statement),
+                            // As are data statements and assertions
+                            not(Data::parse_statement),
+                            not(Assert::parse_statement),
+                            take_while1(|c| c != b'\r' && c != b'\n'),
+                        )), |v| SymbolKind::Value(Name(v.3)) ),
+                    )),
                 )),
             ))
         )(s)?;
 
-        println!("indent: {} kind: {:#08x?}", indent, kind);
+        trace!("indent: {} kind: {:#08x?}", indent, kind);
+
+        let mut name = wrapped;
+
+        // Objects may be followed by their size before relaxing
+        if let SymbolKind::Object{ pre_relax_size, .. } = &mut kind {
+            if let Ok((o1, v)) = parse_pre_relax_size(o) {
+                *pre_relax_size = Some(v);
+                o = o1;
+            }
+        }
 
         // Then, check whether the next line is relevant
         let r = context(
@@ -69,27 +92,16 @@ impl <'a> Symbol<'a> {
             ))
         )(o);
 
-        let mut name = None;
-
-        match r {
-            Ok((o1, (_, addr1, _, val1))) if addr1 == addr => {
-                // Set function name
+        // Set function name where this matches the object address
+        if let Ok((o1, (_, addr1, _, val1))) = r {
+            if addr1 == addr && name.is_none() {
                 name = Some(val1);
 
-                println!("Found name: {}", val1);
-
-                // Update remainder
-                o = o1;
-            },
-            Ok((o1, (_, addr1, _, val1))) => {
-
-                println!("Found attribute: 0x%{:x} {}", addr1, val1);
-                // TODO
+                trace!("Found name: {}", val1);
 
                 // Update remainder
                 o = o1;
             }
-            _ => (),
         }
 
         Ok((
@@ -103,7 +115,28 @@ impl <'a> Symbol<'a> {
     }
 
 
-    pub fn parse_many(s: &'a str) -> IResult<&'a str, Vec<Symbol>, ErrorTree<&'a str>> {
+    /// Parse the size and (optional) file of an object
+    fn parse_object(s: &'a [u8]) -> IResult<&'a [u8], SymbolKind<'a>, ErrorTree<&'a [u8]>> {
+        map(tuple((
+            // Data statements share the size column (ie. `0x4 LONG 0x0`)
+            not(Data::parse_statement),
+            parse_hex,       // Size
+            space0,
+            opt(InputFile::parse), // File path, extending to the end of the line
+            eol,
+        )), |(_, size, _, source, _)| SymbolKind::Object{ size, pre_relax_size: None, source } )(s)
+    }
+
+    /// Parse a long object name, wrapped onto its own line
+    fn parse_wrapped_name(s: &'a [u8]) -> IResult<&'a [u8], Name<'a>, ErrorTree<&'a [u8]>> {
+        delimited(
+            space1,
+            parse_path,
+            line_ending,
+        )(s)
+    }
+
+    pub fn parse_many(s: &'a [u8]) -> IResult<&'a [u8], Vec<Symbol<'a>>, ErrorTree<&'a [u8]>> {
 
         let (rem, output) = context("symbols", 
             many0(tuple((
@@ -121,18 +154,18 @@ impl <'a> Symbol<'a> {
 }
 
 /// Calculate indentation level
-pub fn get_indent<'a>(s: &'a str) -> IResult<&'a str, usize, ErrorTree<&'a str>> {
+pub fn get_indent(s: &[u8]) -> IResult<&[u8], usize, ErrorTree<&[u8]>> {
     // Fetch indentation characters
     let (o, spaces) = context(
         "indentation",
-        take_while1(|c| c == ' ' || c == '\t')
+        take_while1(|c| c == b' ' || c == b'\t')
     )(s)?;
 
     let mut n = 0;
-    for s in spaces.chars() {
+    for s in spaces {
         n += match s {
-            ' ' => 1,
-            '\t' => 4,
+            b' ' => 1,
+            b'\t' => 4,
             _ => 0,
         }
     }
@@ -143,57 +176,57 @@ pub fn get_indent<'a>(s: &'a str) -> IResult<&'a str, usize, ErrorTree<&'a str>>
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::objects::FilePath;
 
-    use nom::multi::many0;
     use pretty_assertions::assert_eq;
 
     const SYMBOLS: &[(Symbol, &str)] = &[
         (
             Symbol{
-                name: Some("norcow_set"),
+                name: Some(Name::new("norcow_set")),
                 addr: 0x0000000008042108,
                 kind: SymbolKind::Object{
                     size: 0x30,
-                    source: Some("build/firmware/vendor/trezor-storage/norcow.o"),
+                    pre_relax_size: None,
+                    source: Some(InputFile::Path(FilePath::new("build/firmware/vendor/trezor-storage/norcow.o"))),
                 },
             },
 "   0x0000000008042108       0x30 build/firmware/vendor/trezor-storage/norcow.o
     0x0000000008042108                norcow_set
 "
-        ), (
-            Symbol{
-                addr: 0x0000000020030000,
-                name: None,
-                kind: SymbolKind::Value("main_stack_base = (ORIGIN (SRAM) + LENGTH (SRAM))")
-            },
-"    0x0000000020030000                main_stack_base = (ORIGIN (SRAM) + LENGTH (SRAM))"
         ), (
             Symbol{
                 addr: 0x0000000008040a00,
                 name: None,
-                kind: SymbolKind::Value("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end")
+                kind: SymbolKind::Value(Name::new("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end"))
             },
 " 0x0000000008040a00                _binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end
 "
         ), (
             Symbol{
-                addr: 0x00000000080fde08,
+                addr: 0x0000000008120000,
                 name: None,
-                kind: SymbolKind::Value("data_lma = LOADADDR (.data)")
+                kind: SymbolKind::Object{
+                    size: 0xf9d8,
+                    pre_relax_size: Some(0xff3c),
+                    source: Some(InputFile::Path(FilePath::new("build/firmware/frozen_mpy.o"))),
+                },
             },
-" 0x00000000080fde08                data_lma = LOADADDR (.data)
+" 0x0000000008120000     0xf9d8 build/firmware/frozen_mpy.o
+                         0xff3c (size before relaxing)
 "
         ), (
             Symbol{
-                addr: 0x0000000008120000,
-                name: None,
+                addr: 0x0000000008040fac,
+                name: Some(Name::new(".text.mod_trezorconfig_wipe")),
                 kind: SymbolKind::Object{
-                    size: 0xf9d8,
-                    source: Some("build/firmware/frozen_mpy.o"),
+                    size: 0x30,
+                    pre_relax_size: None,
+                    source: Some(InputFile::Path(FilePath::new("build/firmware/embed/extmod/modtrezorconfig/modtrezorconfig.o"))),
                 },
             },
-" 0x0000000008120000     0xf9d8 build/firmware/frozen_mpy.o
-                         0xff3c (size before relaxing))
+" .text.mod_trezorconfig_wipe
+                0x0000000008040fac       0x30 build/firmware/embed/extmod/modtrezorconfig/modtrezorconfig.o
 "
         ),
     ];
@@ -201,11 +234,18 @@ mod test {
     #[test]
     fn parse_symbols() {
         for (v, raw) in SYMBOLS {
-            let (_, p) = Symbol::parse(raw).unwrap();
+            let (_, p) = Symbol::parse(raw.as_bytes()).unwrap();
             assert_eq!(&p, v);
         }
     }
 
+    #[test]
+    fn reject_assignments() {
+        // Assignments are parsed as `Assignment`s rather than symbols
+        assert!(Symbol::parse(b"    0x0000000020030000                main_stack_base = (ORIGIN (SRAM) + LENGTH (SRAM))").is_err());
+        assert!(Symbol::parse(b" 0x00000000080fde08                data_lma = LOADADDR (.data)\n").is_err());
+    }
+
     #[test]
     fn parse_chained_symbols() {
 
@@ -218,16 +258,16 @@ mod test {
             Symbol{
                 name: None,
                 addr: 0x0000000008040000,
-                kind: SymbolKind::Value("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_start"),
+                kind: SymbolKind::Value(Name::new("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_start")),
             },
             Symbol{
                 name: None,
                 addr: 0x0000000008040a00,
-                kind: SymbolKind::Value("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end"),
+                kind: SymbolKind::Value(Name::new("_binary_embed_vendorheader_vendorheader_unsafe_signed_prod_bin_end")),
             },
         ];
 
-        let p = Symbol::parse_many(raw).unwrap();
+        let p = Symbol::parse_many(raw.as_bytes()).unwrap();
 
         assert_eq!(p.1, v);
 
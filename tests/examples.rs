@@ -1,16 +1,943 @@
 use mapfile::*;
+use mapfile::objects::*;
 
+// Note: `maps/trezor.map` is not included in the repository, so is not listed here
 const EXAMPLES: &[&str] = &[
     "maps/partial.map",
-    "maps/trezor.map",
 ];
 
 #[test]
 fn parse_examples() {
     for e in EXAMPLES {
         // Read in mapfile
-        let d = std::fs::read_to_string(e).unwrap();
+        let d = std::fs::read(e).unwrap();
         // Attempt to parse
-        let _m = MapFile::parse(&d).unwrap();
+        let _m = MapFile::parse_bytes(&d).unwrap();
+    }
+}
+
+/// Parse the partial example map, shared by the `partial_*` tests
+fn partial_map() -> MapFile<'static> {
+    MapFile::parse(include_str!("../maps/partial.map")).unwrap()
+}
+
+#[test]
+fn parse_partial_sections() {
+    let m = partial_map();
+
+    let names: Vec<_> = m.sections.iter().map(|s| s.name ).collect();
+    assert_eq!(names, &[
+        Some(Name::new(".vendorheader")),
+        Some(Name::new(".header")),
+        Some(Name::new(".flash2")),
+        Some(Name::new(".flash")),
+    ]);
+
+    let flash = m.section(".flash").unwrap();
+    assert_eq!(flash.addr, Some(0x0000000008040e00));
+    assert_eq!(flash.size, Some(0xbd000));
+
+    let objects: Vec<_> = flash.objects().map(|o| o.name ).collect();
+    assert_eq!(objects, &[Some(Name::new(".vector_table")), Some(Name::new(".text.mod_trezorconfig_wipe"))]);
+
+    let vector_table = flash.objects().next().unwrap();
+    let rule = flash.rule(vector_table).unwrap();
+    assert_eq!(rule.file.name, "*");
+    assert_eq!(rule.sections[0].name, ".vector_table");
+}
+
+#[test]
+fn partial_relaxation() {
+    let m = partial_map();
+
+    let rodata = m.section(".flash2").unwrap().objects().next().unwrap();
+    assert_eq!(rodata.pre_relax_size, Some(0xff3c));
+
+    assert_eq!(m.relaxation_by_section().get(&Name::new(".flash2")), Some(&(0xff3c - 0xf9d8)));
+    assert_eq!(m.relaxation_by_file().get(&InputFile::Path(FilePath::new("build/firmware/frozen_mpy.o"))), Some(&(0xff3c - 0xf9d8)));
+    assert_eq!(m.relaxation_by_archive().get(&FilePath::new("build/firmware/frozen_mpy.o")), Some(&(0xff3c - 0xf9d8)));
+}
+
+#[test]
+fn partial_padding() {
+    let m = partial_map();
+
+    let fills: Vec<_> = m.section(".flash2").unwrap().fills().cloned().collect();
+    assert_eq!(fills, &[Fill{ addr: 0x0000000008138bb9, size: 0x1, pattern: None }]);
+
+    assert_eq!(m.padding_by_section().get(&Name::new(".flash2")), Some(&0x1));
+    assert_eq!(m.padding_by_region().get(&Name::new("FLASH2")), Some(&0x1));
+}
+
+const LOAD_ADDRESS: &str = "Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x0000000008040000 0x00000000000c0000 xr
+SRAM             0x0000000020000000 0x0000000000030000 awl
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+LOAD build/firmware/embed/firmware/main.o
+
+.flash          0x0000000008040000      0x400
+ *(.text*)
+ .text          0x0000000008040000      0x400 build/firmware/embed/firmware/main.o
+
+.data           0x0000000020000000      0x200 load address 0x0000000008040400
+ *(.data*)
+ .data          0x0000000020000000      0x200 build/firmware/embed/firmware/main.o
+";
+
+#[test]
+fn parse_load_addresses() {
+    let m = MapFile::parse(LOAD_ADDRESS).unwrap();
+
+    let data = m.section(".data").unwrap();
+    assert_eq!(data.addr, Some(0x0000000020000000));
+    assert_eq!(data.lma(), Some(0x0000000008040400));
+
+    // .data is counted against both its load and runtime regions
+    let usage = m.usage_by_region();
+    assert_eq!(usage.get(&Name::new("SRAM")), Some(&0x200));
+    assert_eq!(usage.get(&Name::new("FLASH")), Some(&(0x400 + 0x200)));
+}
+
+// Generated by GNU ld 2.40 (`ld -m elf_i386 -T link.ld -Map=link.map --cref`)
+const GENERATED: &str = "
+Discarded input sections
+
+ .comment       0x00000000       0x28 a.o
+ .note.GNU-stack
+                0x00000000        0x0 a.o
+ .eh_frame      0x00000000       0x40 a.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00010000         xr
+SRAM             0x20000000         0x00004000         xrw
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+
+.text           0x08000000       0x12
+ *(.text*)
+ .text          0x08000000       0x12 a.o
+                0x08000000                heap
+                0x08000006                main
+ *(.rodata*)
+
+.iplt           0x08000012        0x0
+ .iplt          0x08000012        0x0 a.o
+
+.rel.dyn        0x08000014        0x0
+ .rel.got       0x08000014        0x0 a.o
+ .rel.iplt      0x08000014        0x0 a.o
+ .rel.text      0x08000014        0x0 a.o
+
+.empty
+ *(.nothing)
+
+.data           0x20000000        0xc load address 0x08000012
+ *(.data*)
+ .data          0x20000000        0xc a.o
+                0x20000000                big
+                0x20000008                counter
+
+.got            0x2000000c        0x0 load address 0x0800001e
+ .got           0x2000000c        0x0 a.o
+
+.got.plt        0x2000000c        0x0 load address 0x0800001e
+ .got.plt       0x2000000c        0x0 a.o
+
+.igot.plt       0x2000000c        0x0 load address 0x0800001e
+ .igot.plt      0x2000000c        0x0 a.o
+
+.bss            0x2000000c        0x4 load address 0x0800001e
+ *(.bss*)
+ .bss           0x2000000c        0x4 a.o
+                0x2000000c                zeroed
+ *(COMMON)
+                0x20000010                        PROVIDE (end = .)
+                [!provide]                        PROVIDE (unused_sym = .)
+
+/DISCARD/
+ *(.comment)
+ *(.note*)
+ *(.eh_frame*)
+LOAD a.o
+OUTPUT(r.elf elf32-i386)
+
+Cross Reference Table
+
+Symbol                                            File
+_GLOBAL_OFFSET_TABLE_                             a.o
+big                                               a.o
+counter                                           a.o
+end                                               a.o
+heap                                              a.o
+main                                              a.o
+zeroed                                            a.o
+";
+
+#[test]
+fn parse_empty_sections() {
+    let m = MapFile::parse(GENERATED).unwrap();
+
+    // Empty and discarded sections are listed by name, with their rules
+    for n in [".empty", "/DISCARD/"] {
+        let s = m.section(n).unwrap();
+        assert_eq!((s.addr, s.size), (None, None));
+        assert!(!s.rules.is_empty());
+        assert!(s.entries.is_empty());
+    }
+    assert_eq!(m.section(".data").unwrap().addr, Some(0x20000000));
+}
+
+#[test]
+fn noload_usage() {
+    let m = MapFile::parse(GENERATED).unwrap();
+
+    // `.bss (NOLOAD)` is listed without its type, and with a load address following `.data`
+    let bss = m.section(".bss").unwrap();
+    assert_eq!((bss.kind, bss.load_addr), (None, Some(0x0800001e)));
+    assert_eq!(bss.class(), SectionClass::ZeroInit);
+
+    let usage = m.usage_by_region();
+    assert_eq!(usage.get(&Name::new("FLASH")), Some(&(0x12 + 0xc)));
+    assert_eq!(usage.get(&Name::new("SRAM")), Some(&(0xc + 0x4)));
+}
+
+// Program property merging on x86 hosts, from GNU ld 2.40
+const PROPERTIES: &str = "
+Merging program properties
+
+Removed property 0xc0000002 to merge /usr/lib/gcc/x86_64-linux-gnu/12/../../../x86_64-linux-gnu/Scrt1.o (not found) and /usr/lib/gcc/x86_64-linux-gnu/12/crtbeginS.o (0x3)
+Removed property 0xc0000002 to merge /usr/lib/gcc/x86_64-linux-gnu/12/../../../x86_64-linux-gnu/Scrt1.o (not found) and /usr/lib/gcc/x86_64-linux-gnu/12/crtendS.o (0x3)
+
+As-needed library included to satisfy reference by file (symbol)
+
+libc.so.6                     /usr/lib/gcc/x86_64-linux-gnu/12/../../../x86_64-linux-gnu/Scrt1.o (__libc_start_main@@GLIBC_2.34)
+Memory Configuration
+
+Name             Origin             Length             Attributes
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+LOAD /usr/lib/gcc/x86_64-linux-gnu/12/../../../x86_64-linux-gnu/Scrt1.o
+";
+
+#[test]
+fn parse_unindented_blocks() {
+    let m = MapFile::parse(PROPERTIES).unwrap();
+
+    assert_eq!(m.other.len(), 1);
+    assert_eq!(m.other[0].heading, Name::new("Merging program properties"));
+    assert!(m.other[0].body.starts_with("Removed property 0xc0000002"));
+    assert_eq!(m.other[0].body.as_bytes().split(|c| *c == b'\n' ).count(), 2);
+    assert_eq!(m.as_needed.len(), 1);
+    assert_eq!(m.files.len(), 1);
+}
+
+// Cortex-M style layout with `.data` loaded from flash, generated by GNU ld 2.40
+const STM32_LAYOUT: &str = "
+Discarded input sections
+
+ .comment       0x00000000       0x28 m.o
+ .note.GNU-stack
+                0x00000000        0x0 m.o
+ .eh_frame      0x00000000       0x2c m.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00010000         xr
+RAM              0x20000000         0x00004000         xrw
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+                0x00000200                        _Min_Heap_Size = 0x200
+                0x00000400                        _Min_Stack_Size = 0x400
+
+.text           0x08000000        0xc
+ *(.text*)
+ .text          0x08000000        0xc m.o
+                0x08000000                main
+ *(.rodata*)
+                0x0800000c                        _sidata = LOADADDR (.data)
+
+.data           0x20000000        0x4 load address 0x0800000c
+                0x20000000                        _sdata = .
+ *(.data*)
+ .data          0x20000000        0x4 m.o
+                0x20000000                counter
+                0x20000004                        _edata = .
+
+.bss            0x20000004        0x4 load address 0x08000010
+                0x20000004                        _sbss = .
+ *(.bss*)
+ .bss           0x20000004        0x4 m.o
+                0x20000004                zeroed
+ *(COMMON)
+                0x20000008                        _ebss = .
+
+._user_heap_stack
+                0x20000008      0x600 load address 0x08000010
+                0x20000008                        . = ALIGN (0x8)
+                [!provide]                        PROVIDE (end = .)
+                [!provide]                        PROVIDE (_end = .)
+                0x20000208                        . = (. + _Min_Heap_Size)
+ *fill*         0x20000008      0x200 
+                0x20000608                        . = (. + _Min_Stack_Size)
+ *fill*         0x20000208      0x400 
+                0x20000608                        . = ALIGN (0x8)
+
+/DISCARD/
+ *(.comment)
+ *(.note*)
+ *(.eh_frame*)
+ *(.got*)
+ *(.iplt)
+ *(.rel*)
+ *(.igot*)
+LOAD m.o
+OUTPUT(stm.elf elf32-i386)
+";
+
+#[test]
+fn stm32_usage() {
+    let m = MapFile::parse(STM32_LAYOUT).unwrap();
+
+    // ld reports load addresses for sections following `.data`, though only `.data` is loaded
+    assert_eq!(m.section(".bss").unwrap().load_addr, Some(0x08000010));
+    assert_eq!(m.section("._user_heap_stack").unwrap().class(), SectionClass::ZeroInit);
+
+    let usage = m.usage_by_region();
+    assert_eq!(usage.get(&Name::new("FLASH")), Some(&(0xc + 0x4)));
+    assert_eq!(usage.get(&Name::new("RAM")), Some(&(0x4 + 0x4 + 0x600)));
+}
+
+#[test]
+fn unapplied_provides() {
+    let m = MapFile::parse(STM32_LAYOUT).unwrap();
+
+    // `[!provide]` assignments were not applied by the linker
+    let symbols = m.linker_symbols();
+    assert!(!symbols.contains_key(&Name::new("end")));
+    assert!(!symbols.contains_key(&Name::new("_end")));
+    assert_eq!(symbols.get(&Name::new("_ebss")).and_then(|a| a.value ), Some(0x20000008));
+    assert!(m.all_assignments().any(|a| a.symbol == "end" && !a.is_applied() ));
+}
+
+const REORDERED: &str = "
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x0000000008000000 0x0000000000100000 xr
+*default*        0x0000000000000000 0xffffffffffffffff
+
+As-needed library included to satisfy reference by file (symbol)
+
+libm.so.6                     build/main.o (sqrt@@GLIBC_2.2.5)
+
+There are no discarded input sections
+
+Some future block
+ with indented contents
+
+Linker script and memory map
+
+LOAD build/main.o
+START GROUP
+LOAD /usr/lib/arm-none-eabi/lib/libc.a
+LOAD /usr/lib/arm-none-eabi/lib/libm.a
+END GROUP
+
+.text           0x0000000008000000      0x100
+ *(.text*)
+ .text          0x0000000008000000      0x100 build/main.o
+OUTPUT(build/main.elf elf32-littlearm)
+LOAD linker stubs
+
+Cross Reference Table
+
+Symbol                                            File
+main                                              build/main.o
+                                                  build/startup.o
+";
+
+#[test]
+fn parse_reordered_blocks() {
+    let m = MapFile::parse(REORDERED).unwrap();
+
+    assert!(m.references.is_empty());
+    assert!(m.discarded.is_empty());
+    assert_eq!(m.as_needed, &[AsNeededInfo{
+        library: FilePath::new("libm.so.6"),
+        file: Some(InputFile::Path(FilePath::new("build/main.o"))),
+        symbol: Name::new("sqrt@@GLIBC_2.2.5"),
+    }]);
+    assert_eq!(m.memory.len(), 2);
+    assert_eq!(m.files.len(), 3);
+    assert_eq!(m.group(0).map(|f| f.name.path() ).collect::<Vec<_>>(), &[
+        FilePath::new("/usr/lib/arm-none-eabi/lib/libc.a"),
+        FilePath::new("/usr/lib/arm-none-eabi/lib/libm.a"),
+    ]);
+    assert_eq!(m.output_file(), Some(FilePath::new("build/main.elf")));
+    assert_eq!(m.output_target().map(|t| (t.arch(), t.endian()) ), Some(("arm", Some(Endian::Little))));
+    assert_eq!(m.commands.last(), Some(&Command::LoadStubs));
+    assert_eq!(m.section(".text").unwrap().size, Some(0x100));
+
+    assert_eq!(m.defined_by("main"), Some(InputFile::Path(FilePath::new("build/main.o"))));
+    assert_eq!(m.referenced_by("main"), &[InputFile::Path(FilePath::new("build/startup.o"))]);
+    assert!(m.referenced_by("missing").is_empty());
+
+    assert_eq!(m.other, &[
+        RawBlock{ heading: Name::new("Some future block"), body: Name::new(" with indented contents") },
+    ]);
+}
+
+const CROSS_REFERENCES: &str = "
+Memory Configuration
+
+Name             Origin             Length             Attributes
+SRAM             0x20000000         0x00010000         xrw
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+                0x20010000                _estack = (ORIGIN (SRAM) + LENGTH (SRAM))
+
+.text           0x08000000       0x20
+ *(.text*)
+ .text          0x08000000       0x10 build/a.o
+                0x08000000                handler
+ .text          0x08000010       0x10 build/b.o
+                0x08000010                handler
+
+Cross Reference Table
+
+Symbol                                            File
+_estack                                           build/startup.o
+handler                                           build/a.o
+                                                  build/b.o
+                                                  build/main.o
+";
+
+#[test]
+fn cross_reference_definitions() {
+    let m = MapFile::parse(CROSS_REFERENCES).unwrap();
+
+    // Linker script symbols are listed with their references only
+    assert!(m.definitions("_estack").is_empty());
+    assert_eq!(m.defined_by("_estack"), None);
+    assert_eq!(m.referenced_by("_estack"), &[InputFile::Path(FilePath::new("build/startup.o"))]);
+
+    // Multiple definitions (ie. with `--allow-multiple-definition`) are not references
+    assert_eq!(m.definitions("handler"), &[
+        InputFile::Path(FilePath::new("build/a.o")),
+        InputFile::Path(FilePath::new("build/b.o")),
+    ]);
+    assert_eq!(m.referenced_by("handler"), &[InputFile::Path(FilePath::new("build/main.o"))]);
+}
+
+// Object definition overriding a linker script `PROVIDE`, generated by GNU ld 2.40
+const PROVIDED: &str = "
+Discarded input sections
+
+ .comment       0x00000000       0x28 use.o
+ .note.GNU-stack
+                0x00000000        0x0 use.o
+ .eh_frame      0x00000000       0x2c use.o
+ .comment       0x00000000       0x28 heap.o
+ .note.GNU-stack
+                0x00000000        0x0 heap.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00010000         xr
+RAM              0x20000000         0x00004000         xrw
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+                0x00000200                        _Min_Heap_Size = 0x200
+                0x00000400                        _Min_Stack_Size = 0x400
+
+.text           0x08000000        0x6
+ *(.text*)
+ .text          0x08000000        0x6 use.o
+                0x08000000                heap_start
+ .text          0x08000006        0x0 heap.o
+ *(.rodata*)
+                0x08000006                        _sidata = LOADADDR (.data)
+
+.data           0x20000000        0x4 load address 0x08000006
+                0x20000000                        _sdata = .
+ *(.data*)
+ .data          0x20000000        0x0 use.o
+ .data          0x20000000        0x4 heap.o
+                0x20000000                end
+                0x20000004                        _edata = .
+
+.bss            0x20000004        0x0 load address 0x0800000a
+                0x20000004                        _sbss = .
+ *(.bss*)
+ .bss           0x20000004        0x0 use.o
+ .bss           0x20000004        0x0 heap.o
+ *(COMMON)
+                0x20000004                        _ebss = .
+
+._user_heap_stack
+                0x20000004      0x604 load address 0x0800000a
+                0x20000008                        . = ALIGN (0x8)
+ *fill*         0x20000004        0x4 
+                [!provide]                        PROVIDE (end = .)
+                [!provide]                        PROVIDE (_end = .)
+                0x20000208                        . = (. + _Min_Heap_Size)
+ *fill*         0x20000008      0x200 
+                0x20000608                        . = (. + _Min_Stack_Size)
+ *fill*         0x20000208      0x400 
+                0x20000608                        . = ALIGN (0x8)
+
+/DISCARD/
+ *(.comment)
+ *(.note*)
+ *(.eh_frame*)
+ *(.got*)
+ *(.iplt)
+ *(.rel*)
+ *(.igot*)
+LOAD use.o
+LOAD heap.o
+OUTPUT(prov.elf elf32-i386)
+
+Cross Reference Table
+
+Symbol                                            File
+_GLOBAL_OFFSET_TABLE_                             use.o
+end                                               heap.o
+                                                  use.o
+heap_start                                        use.o
+";
+
+#[test]
+fn provided_definitions() {
+    let m = MapFile::parse(PROVIDED).unwrap();
+
+    // `end` is provided by the script, but defined by an object so the assignment is skipped
+    assert!(m.all_assignments().any(|a| a.symbol == "end" && !a.is_applied() ));
+    assert_eq!(m.definitions("end"), &[InputFile::Path(FilePath::new("heap.o"))]);
+    assert_eq!(m.referenced_by("end"), &[InputFile::Path(FilePath::new("use.o"))]);
+}
+
+#[test]
+fn partial_linker_symbols() {
+    let m = partial_map();
+
+    assert_eq!(m.assignments.len(), 16);
+
+    let symbols = m.linker_symbols();
+    let estack = symbols.get(&Name::new("_estack")).unwrap();
+    assert_eq!(estack.expr, "main_stack_base");
+    assert_eq!(estack.value, Some(0x0000000020030000));
+    assert!(!estack.provide);
+
+    // Location counter moves are not symbols
+    assert!(!symbols.contains_key(&Name::new(".")));
+    assert_eq!(m.all_assignments().filter(|a| a.is_location_counter() ).count(), 1);
+}
+
+#[test]
+fn partial_expressions() {
+    let m = partial_map();
+
+    // Assignments evaluate to the values reported by the linker, where resolvable
+    for a in &m.assignments {
+        match m.verify(a) {
+            Ok(v) => assert!(v, "{} = {}", a.symbol, a.expr),
+            // The partial map omits these sections
+            Err(e) => assert!(matches!(e, EvalError::UnknownSection(".heap" | ".data")), "{} = {}", a.symbol, a.expr),
+        }
+    }
+
+    let codelen = m.linker_symbols()[&Name::new("_codelen")];
+    assert_eq!(m.evaluate(codelen), Ok(0x0000000000121c00));
+
+    // Dependencies are followed through other linker symbols
+    let deps: Vec<_> = m.symbol_dependencies("_estack").into_iter().collect();
+    assert_eq!(deps, &[Dependency::Region("SRAM"), Dependency::Symbol("main_stack_base")]);
+
+    let deps: Vec<_> = m.symbol_dependencies("_codelen").into_iter().collect();
+    assert_eq!(deps, &[
+        Dependency::Region("FLASH"),
+        Dependency::Section(".flash2"),
+        Dependency::Section(".header"),
+        Dependency::Section(".vendorheader"),
+    ]);
+}
+
+#[test]
+fn partial_archive_references() {
+    let m = partial_map();
+
+    assert_eq!(m.references.len(), 2);
+
+    // Why was compiler_builtins linked in?
+    let r = m.archive_references("build/firmware/rust/thumbv7em-none-eabihf/release/libtrezor_lib.a")
+        .find(|r| r.member.starts_with("compiler_builtins-") )
+        .unwrap();
+    assert_eq!(r.file, Some(InputFile::Path(FilePath::new("build/firmware/vendor/micropython/extmod/moductypes.o"))));
+    assert_eq!(r.symbol, "__aeabi_f2d");
+    assert_eq!(r.input().archive(), Some(r.archive));
+}
+
+const NON_ALLOC: &str = "Memory Configuration
+
+Name             Origin             Length             Attributes
+ITCM             0x0000000000000000 0x0000000000010000 xr
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+.text           0x0000000000000000      0x100
+ *(.text*)
+ .text          0x0000000000000000      0x100 build/main.o
+
+.bss            0x0000000000000100       0x40
+ .bss           0x0000000000000100       0x40 build/main.o
+
+.comment        0x0000000000000000       0x49
+ *(.comment)
+ .comment       0x0000000000000000       0x49 build/main.o
+                                         0x4a (size before relaxing)
+
+.ARM.attributes
+                0x0000000000000000       0x30
+ *(.ARM.attributes)
+ .ARM.attributes
+                0x0000000000000000       0x30 build/main.o
+
+.debug_info     0x0000000000000000     0x1234
+ *(.debug_info .gnu.linkonce.wi.*)
+ .debug_info    0x0000000000000000     0x1234 build/main.o
+";
+
+#[test]
+fn non_alloc_sections() {
+    let m = MapFile::parse(NON_ALLOC).unwrap();
+
+    let classes: Vec<_> = m.sections.iter().map(|s| (s.name.unwrap(), s.class()) ).collect();
+    assert_eq!(classes, &[
+        (Name::new(".text"), SectionClass::Code),
+        (Name::new(".bss"), SectionClass::ZeroInit),
+        (Name::new(".comment"), SectionClass::Metadata),
+        (Name::new(".ARM.attributes"), SectionClass::Metadata),
+        (Name::new(".debug_info"), SectionClass::Debug),
+    ]);
+
+    // Debug and metadata sections at address zero are not counted against memory
+    assert_eq!(m.alloc_sections().count(), 2);
+    assert_eq!(m.usage_by_region().get(&Name::new("ITCM")), Some(&0x140));
+    assert!(m.relaxation_by_section().is_empty());
+}
+
+const OVERLAYS: &str = "Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x0000000008000000 0x0000000000100000 xr
+RAM              0x0000000020000000 0x0000000000020000 xrw
+*default*        0x0000000000000000 0xffffffffffffffff
+
+Linker script and memory map
+
+.text           0x0000000008000000      0x100
+ .text          0x0000000008000000      0x100 build/main.o
+
+.ov1            0x0000000020000000       0x80 load address 0x0000000008000100
+ .text.ov1      0x0000000020000000       0x80 build/ov1.o
+
+.ov2            0x0000000020000000       0x40 load address 0x0000000008000180
+ .text.ov2      0x0000000020000000       0x40 build/ov2.o
+
+.noinit (NOLOAD)
+                0x0000000020000080       0x80 load address 0x00000000080001c0
+ .noinit        0x0000000020000080       0x80 build/main.o
+
+.data           0x0000000020000100       0x10 load address 0x00000000080001c0
+ .data          0x0000000020000100       0x10 build/main.o
+";
+
+#[test]
+fn overlay_sections() {
+    let m = MapFile::parse(OVERLAYS).unwrap();
+
+    let noinit = m.section(".noinit").unwrap();
+    assert_eq!(noinit.kind, Some(SectionKind::NoLoad));
+    assert_eq!(noinit.addr, Some(0x0000000020000080));
+
+    assert_eq!(m.overlays(), &[Overlay{ addr: 0x0000000020000000, sections: vec![Name::new(".ov1"), Name::new(".ov2")] }]);
+
+    // Overlay members share an address without colliding
+    assert!(m.collisions().is_empty());
+
+    let d = OVERLAYS.replace("0x0000000020000100       0x10", "0x00000000200000f0       0x10");
+    let m1 = MapFile::parse(&d).unwrap();
+    let collisions: Vec<_> = m1.collisions().iter().map(|(a, b)| (a.name, b.name) ).collect();
+    assert_eq!(collisions, &[(Some(Name::new(".noinit")), Some(Name::new(".data")))]);
+
+    // Empty sections sharing an address are not overlays
+    let m1 = MapFile::parse("Linker script and memory map
+
+.noinit (NOLOAD)
+                0x0000000020000000        0x0
+ .noinit        0x0000000020000000        0x0 build/main.o
+
+.data           0x0000000020000000       0x10 load address 0x0000000008000100
+ .data          0x0000000020000000       0x10 build/main.o
+").unwrap();
+    assert_eq!(m1.section(".noinit").unwrap().size, Some(0));
+    assert!(m1.overlays().is_empty());
+
+    // Zero-initialised sections are not counted against flash, overlays use the largest member at runtime
+    let usage = m.usage_by_region();
+    assert_eq!(usage.get(&Name::new("FLASH")), Some(&(0x100 + 0x80 + 0x40 + 0x10)));
+    assert_eq!(usage.get(&Name::new("RAM")), Some(&(0x80 + 0x80 + 0x10)));
+}
+
+const NARROW: &str = "Archive member included to satisfy reference by file (symbol)
+
+/usr/lib/gcc/arm-none-eabi/9.2.1/thumb/v7e-m+fp/hard/libgcc.a(_arm_addsubdf3.o)
+                              build/main.o (__aeabi_dadd)
+
+Discarded input sections
+
+ .text          0x00000000        0x0 build/main.o
+ .data          0x00000000        0x0 build/main.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00100000         xr
+RAM              0x20000000         0x00020000         xrw
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+LOAD build/startup.o
+LOAD build/main.o
+                0x20020000                _estack = (ORIGIN (RAM) + LENGTH (RAM))
+
+.isr_vector     0x08000000      0x188
+                0x08000000                . = ALIGN (0x4)
+ *(.isr_vector)
+ .isr_vector    0x08000000      0x188 build/startup.o
+                0x08000000                g_pfnVectors
+                0x08000188                . = ALIGN (0x4)
+
+.text           0x08000188      0x2a0
+ *(.text*)
+ .text.main     0x08000188       0x2c build/main.o
+                0x08000188                main
+ *fill*         0x080001b4        0x4 
+ .text.Reset_Handler
+                0x080001b8      0x270 build/startup.o
+                                0x274 (size before relaxing)
+                0x080001b8                Reset_Handler
+
+.data           0x20000000       0x10 load address 0x08000428
+ *(.data*)
+ .data          0x20000000       0x10 build/main.o
+OUTPUT(build/firmware.elf elf32-littlearm)
+";
+
+#[test]
+fn parse_narrow_addresses() {
+    let m = MapFile::parse(NARROW).unwrap();
+
+    assert_eq!(m.address_bits, Some(32));
+    assert_eq!(m.format_addr(0x08000188), "0x08000188");
+
+    assert_eq!(m.references.len(), 1);
+    assert_eq!(m.discarded.len(), 2);
+    assert_eq!(m.memory.iter().map(|r| (r.name, r.origin, r.length) ).collect::<Vec<_>>(), &[
+        (Name::new("FLASH"), 0x08000000, 0x00100000),
+        (Name::new("RAM"), 0x20000000, 0x00020000),
+        (Name::new("*default*"), 0x00000000, 0xffffffff),
+    ]);
+
+    let names: Vec<_> = m.sections.iter().map(|s| (s.name, s.addr, s.size) ).collect();
+    assert_eq!(names, &[
+        (Some(Name::new(".isr_vector")), Some(0x08000000), Some(0x188)),
+        (Some(Name::new(".text")), Some(0x08000188), Some(0x2a0)),
+        (Some(Name::new(".data")), Some(0x20000000), Some(0x10)),
+    ]);
+
+    let text = m.section(".text").unwrap();
+    assert_eq!(text.contents_size(), 0x2a0);
+    assert_eq!(text.relaxation_savings(), 0x4);
+    let symbols: Vec<_> = text.symbols().filter_map(|s| match s.kind {
+        SymbolKind::Value(v) => Some((v, s.addr)),
+        _ => None,
+    }).collect();
+    assert_eq!(symbols, &[(Name::new("main"), 0x08000188), (Name::new("Reset_Handler"), 0x080001b8)]);
+
+    assert_eq!(m.verify(&m.assignments[0]), Ok(true));
+    assert_eq!(m.usage_by_region().get(&Name::new("FLASH")), Some(&(0x188 + 0x2a0 + 0x10)));
+    assert_eq!(m.output_target().and_then(|t| t.bits() ), Some(32));
+
+    // Wide maps are detected from their padded addresses
+    let m = partial_map();
+    assert_eq!(m.address_bits, Some(64));
+    assert_eq!(m.format_addr(0x08040000), "0x0000000008040000");
+}
+
+const LATIN1: &[u8] = b"Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00100000         xr
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+LOAD build/caf\xe9/main.o
+
+.text           0x08000000      0x100
+ *(.text*)
+ .text          0x08000000      0x100 build/caf\xe9/main.o
+                0x08000000                d\xe9marrer
+";
+
+// 32-bit map with 64-bit data values, generated by GNU ld 2.40
+const WIDE_VALUES: &str = "
+Discarded input sections
+
+ .data          0x00000000        0x0 q.o
+ .bss           0x00000000        0x0 q.o
+ .comment       0x00000000       0x28 q.o
+ .note.GNU-stack
+                0x00000000        0x0 q.o
+ .eh_frame      0x00000000       0x2c q.o
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00010000         xr
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+
+.text           0x08000000       0x12
+ *(.text*)
+ .text          0x08000000        0x6 q.o
+                0x08000000                main
+                0x08000006        0x8 QUAD 0x123456789abcdef0
+                0x0800000e        0x4 LONG 0x1
+                0x00000001                        ASSERT ((SIZEOF (.text) < 0x123456789a), too big)
+
+/DISCARD/
+ *(.comment)
+ *(.note*)
+ *(.eh_frame*)
+ *(.got*)
+ *(.iplt)
+ *(.rel*)
+ *(.igot*)
+ *(.data*)
+ *(.bss*)
+LOAD q.o
+OUTPUT(q.elf elf32-i386)
+";
+
+#[test]
+fn detect_address_columns() {
+    let m = MapFile::parse(WIDE_VALUES).unwrap();
+
+    // Data values and expressions are not addresses
+    assert_eq!(m.address_bits, Some(32));
+    assert_eq!(m.format_addr(0x08000006), "0x08000006");
+
+    // Nor are values in unrecognised blocks
+    let raw = PROPERTIES
+        .replace("0x0000000000000000 0xffffffffffffffff", "0x00000000         0xffffffff")
+        .replace("0xc0000002", "0x1c0000002");
+    let m = MapFile::parse(&raw).unwrap();
+    assert_eq!(m.address_bits, Some(32));
+}
+
+#[test]
+fn parse_non_utf8() {
+    // Latin-1 paths and symbols are preserved as bytes
+    let m = MapFile::parse_bytes(LATIN1).unwrap();
+
+    let path = FilePath(Name(b"build/caf\xe9/main.o"));
+    assert_eq!(m.files.iter().map(|f| f.name ).collect::<Vec<_>>(), &[InputFile::Path(path)]);
+
+    let text = m.section(".text").unwrap();
+    let o = text.objects().next().unwrap();
+    assert_eq!(o.source, Some(InputFile::Path(path)));
+    assert_eq!(o.source.unwrap().path().as_name().to_str(), None);
+    assert_eq!(o.source.unwrap().path().as_name().to_string_lossy(), "build/caf\u{fffd}/main.o");
+
+    let symbols: Vec<_> = text.symbols().map(|s| s.kind.clone() ).collect();
+    assert_eq!(symbols, &[SymbolKind::Value(Name(b"d\xe9marrer"))]);
+}
+
+const WINDOWS: &str = r"Archive member included to satisfy reference by file (symbol)
+
+C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a(lib_a-memcpy.o)
+                              C:\Users\Jo Bloggs\My Project\build\main.o (memcpy)
+
+Memory Configuration
+
+Name             Origin             Length             Attributes
+FLASH            0x08000000         0x00100000         xr
+*default*        0x00000000         0xffffffff
+
+Linker script and memory map
+
+LOAD C:\Users\Jo Bloggs\My Project\build\main.o
+LOAD C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a
+
+.text           0x08000000      0x110
+ *(.text*)
+ .text          0x08000000      0x100 C:\Users\Jo Bloggs\My Project\build\main.o
+                0x08000000                main
+ .text          0x08000100       0x10 C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a(lib_a-memcpy.o)
+                0x08000100                memcpy
+OUTPUT(C:\Users\Jo Bloggs\My Project\build\firmware.elf elf32-littlearm)
+";
+
+#[test]
+fn parse_windows_paths() {
+    // Windows hosts produce paths with spaces, parentheses and backslashes
+    let libc = FilePath::new(r"C:\Program Files (x86)\GNU Arm Embedded\lib\libc.a");
+    let main = FilePath::new(r"C:\Users\Jo Bloggs\My Project\build\main.o");
+
+    for raw in [WINDOWS.to_string(), WINDOWS.replace('\n', "\r\n")] {
+        let m = MapFile::parse(&raw).unwrap();
+
+        assert_eq!(m.references.len(), 1);
+        assert_eq!(m.references[0].archive, libc);
+        assert_eq!(m.references[0].file, Some(InputFile::Path(main)));
+        assert_eq!(m.references[0].symbol, "memcpy");
+
+        assert_eq!(m.files.iter().map(|f| f.name.path() ).collect::<Vec<_>>(), &[main, libc]);
+
+        let sources: Vec<_> = m.section(".text").unwrap().objects().map(|o| o.source ).collect();
+        assert_eq!(sources, &[
+            Some(InputFile::Path(main)),
+            Some(InputFile::Member{ archive: libc, member: Name::new("lib_a-memcpy.o") }),
+        ]);
+
+        let output = m.output_file().unwrap();
+        assert_eq!(output.normalised(), "C:/Users/Jo Bloggs/My Project/build/firmware.elf");
+        assert_eq!(output.file_name(), Some(Name::new("firmware.elf")));
     }
 }